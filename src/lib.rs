@@ -132,3 +132,4694 @@ generate_client!(my_api,
 ///
 /// This is much less error prone than writing docs by hand.
 generate_docs!(my_api);
+
+
+/// Once we have the function created by `generate_server!` (called `my_api_server` below), we
+/// still need to actually serve it.
+///
+/// Rather than making every application figure out how to open a socket for each web framework,
+/// there could be a small `Server` helper that takes care of binding.  The important thing is that
+/// the socket options are described once, and then each backend (Rocket, Axum, etc) is
+/// responsible for translating them into its own configuration.
+///
+/// -   `dual_stack` listens on both IPv4 and IPv6 with a single `[::]` socket.
+/// -   `backlog`, `nodelay`, and `keepalive` are passed straight through to the TCP socket.
+/// -   `from_listener` lets you hand over a listener you have already bound yourself.
+/// -   `from_systemd` picks up a listener passed in with systemd socket activation (`LISTEN_FDS`),
+///     which lets the server be restarted without dropping connections.
+///
+/// If a backend is not able to honor one of these options, it should be an error at startup,
+/// not something that gets silently ignored.
+fn serve_my_api() {
+    Server::bind("[::]:8080")
+        .dual_stack(true)
+        .backlog(1024)
+        .nodelay(true)
+        .keepalive(Duration::from_secs(60))
+        .serve(my_api_server());
+}
+
+/// This is the same server as above, but using a listener inherited from systemd.
+fn serve_my_api_systemd() {
+    Server::from_systemd(0)
+        .nodelay(true)
+        .serve(my_api_server());
+}
+
+
+/// `Server` should also know how to speak TLS, so that every application doesn't have to glue
+/// rustls onto each backend itself.
+///
+/// The certificate and key are loaded from PEM files at startup.  With `reload_on_sighup`, the
+/// files are read again whenever the process gets a `SIGHUP`, so certificates can be rotated
+/// without a restart.  If the new files fail to parse, the old certificate keeps being used and
+/// the error is logged.
+///
+/// Optionally, client certificates can be required (mTLS).  The verified certificate is mapped
+/// to a principal by a function we provide, and handlers can take that principal as an argument
+/// just like a capture or query parameter:
+///
+/// ```
+/// fn handler_user_create(principal: CertPrincipal, userId: UserId, name: Name) -> User {
+///     ...
+/// }
+/// ```
+fn serve_my_api_tls() {
+    Server::bind("[::]:8443")
+        .tls(
+            Tls::from_pem_files("cert.pem", "key.pem")
+                .reload_on_sighup()
+                .client_auth(
+                    ClientAuth::required("client-ca.pem")
+                        .principal(|cert| CertPrincipal(cert.subject_common_name()))
+                )
+        )
+        .serve(my_api_server());
+}
+
+/// The generated client needs the matching configuration on its side: extra root certificates
+/// for servers using a private CA, and a client certificate to present when the server requires
+/// mTLS.
+fn my_api_client_tls() -> Client {
+    Client::builder("https://api.example.com")
+        .add_root_cert("private-ca.pem")
+        .client_cert("client-cert.pem", "client-key.pem")
+        .build()
+}
+
+
+/// Routes aren't forever.  A route can be marked as deprecated, and additionally given a sunset
+/// date after which it is no longer supported.
+///
+/// Here, `/user/get` is being replaced with `/user/list`.
+///
+/// -   Before the sunset date, the generated server serves the old route normally, but adds the
+///     `Deprecation` and `Sunset` headers (RFC 8594) to every response, along with a `Link` header
+///     pointing at the replacement route.
+///
+/// -   After the sunset date, the behavior depends on `AfterSunset`.  With `AfterSunset::Gone`,
+///     the server stops calling the handler and returns `410 Gone`, with a body pointing at the
+///     replacement route.  With `AfterSunset::KeepServing`, the route keeps working and only the
+///     headers are sent.
+///
+/// The generated docs (and OpenAPI output) show the route as deprecated, along with the sunset
+/// date and the replacement route, so the frontend team knows how long they have to migrate.
+fn my_api_with_sunset() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .deprecated()
+                    .replaced_by("users_list")
+                    .sunset("2021-06-01", AfterSunset::Gone),
+                path("list")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .name("users_list"),
+            ]
+        )
+}
+
+
+/// The generated client doesn't need to send requests over the network itself.  It could be
+/// parameterized over a `Transport`, which takes an HTTP request and returns an HTTP response.
+/// The default transport is a normal HTTP client, but other transports can wrap it.
+///
+/// A `RecordingTransport` passes every request through to the real transport, and writes each
+/// request/response pair to a cassette file.  A `ReplayTransport` reads a cassette file back, and
+/// answers requests from it without touching the network.  A request that doesn't match anything
+/// in the cassette is an error, so tests fail loudly instead of quietly hitting the real server.
+///
+/// This means integration tests against someone else's deployment of our API can be recorded
+/// once, and then replayed deterministically in CI:
+///
+/// ```
+/// #[test]
+/// fn test_users_get() {
+///     let client = Client::builder("https://api.example.com")
+///         .transport(ReplayTransport::from_cassette("tests/cassettes/users_get.json"))
+///         .build();
+///
+///     let users = client.users_get(true);
+///     assert_eq!(users.len(), 3);
+/// }
+/// ```
+///
+/// Requests are matched on method, path, query string, and body.  Headers like `Authorization`
+/// are left out of the cassette file by default, so secrets don't end up committed to the repo.
+fn my_api_client_recording() -> Client {
+    Client::builder("https://api.example.com")
+        .transport(
+            RecordingTransport::new(HttpTransport::default(), "tests/cassettes/users_get.json")
+                .redact_header("Authorization")
+        )
+        .build()
+}
+
+
+/// The server side gets the same idea.  A `RecordingLayer` wraps the generated server, samples
+/// a percentage of incoming requests, and writes them to a `RecordSink` (a file, or something
+/// like a message queue).
+///
+/// The layer is given an `Api` with the same routes as the server, so it knows which parts of a
+/// request are sensitive.  Here that is `my_api_with_sensitive`, which is `my_api` plus the
+/// `.sensitive(..)` declarations.  Any capture, query parameter, or body field declared with
+/// `.sensitive(..)` is replaced with `"<redacted>"` before the request is written out.  Headers
+/// like `Authorization` and `Cookie` are always dropped.  Routes in the server that the given
+/// `Api` doesn't have are an error when the layer is added, so nothing is recorded unredacted by
+/// mistake.
+///
+/// The recorded traffic can then be re-sent against a new build of the server with `replay`.
+/// The replay harness compares the new responses against the recorded ones, and reports every
+/// route where the status code or response body changed:
+///
+/// ```
+/// $ cargo run --bin my-server-replay -- --recording traffic.jsonl --target http://localhost:8080
+/// user_create: 1000 requests, 0 differences
+/// users_get:   2500 requests, 3 differences
+/// ```
+fn my_api_with_sensitive() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .sensitive("name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+fn serve_my_api_recording() {
+    Server::bind("[::]:8080")
+        .layer(
+            RecordingLayer::new(&my_api_with_sensitive(), FileSink::new("traffic.jsonl"))
+                .sample_percent(1)
+        )
+        .serve(my_api_server());
+}
+
+fn replay_my_api_traffic() {
+    replay(my_api_with_sensitive(), Recording::open("traffic.jsonl"), "http://localhost:8080")
+        .report();
+}
+
+
+/// Some GET routes are expensive to compute and rarely change.  A GET route can be marked with
+/// `.cached(ttl)`, and the generated server will cache the handler's result instead of calling
+/// the handler on every request.
+///
+/// The cache key is the route name plus the route's parameters.  Since the server already knows
+/// the declared captures and query parameters, it can normalize them before building the key, so
+/// `/user/get?sort=true` and `/user/get?sort=1` end up as the same entry.
+///
+/// Where the cache lives is pluggable with the `CacheStore` trait.  There would be an in-memory
+/// store built in, and a Redis store could be written outside of this crate:
+///
+/// ```
+/// trait CacheStore {
+///     fn get(&self, key: &CacheKey) -> Option<Vec<u8>>;
+///     fn put(&self, key: &CacheKey, value: Vec<u8>, ttl: Duration);
+///     fn invalidate(&self, key: &CacheKey);
+///     /// Throw away every entry for a route, whatever its parameters.
+///     fn invalidate_all(&self, route_name: &str);
+/// }
+/// ```
+///
+/// Mutating handlers need a way to throw away stale entries.  A handler can take a `Cache`
+/// argument, and invalidate a single entry by route name and parameters with
+/// `cache.invalidate(..)`, or every entry for a route with `cache.invalidate_all(..)`.  The
+/// parameters are normalized the same way as when the key was built, and both the route name and
+/// the parameter names are checked against the `Api`, so a typo here is an error instead of a
+/// cache that never clears.
+fn my_api_cached() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .cached(Duration::from_secs(30)),
+            ]
+        )
+}
+
+fn handler_user_create_cached(cache: Cache, userId: UserId, name: Name) -> User {
+    cache.invalidate("users_get", params!{ sort: true });
+    cache.invalidate("users_get", params!{ sort: false });
+    todo!();
+}
+
+fn serve_my_api_cached() {
+    Server::bind("[::]:8080")
+        .cache_store(InMemoryCacheStore::with_capacity(10_000))
+        .serve(my_api_server());
+}
+
+
+/// Behind a load balancer, the peer address of a connection is the load balancer, not the
+/// actual client.  The `Api` can declare which proxy ranges are trusted.  For requests coming
+/// from a trusted proxy, the real client IP is taken from the `Forwarded` header (or
+/// `X-Forwarded-For` if `Forwarded` is missing), walking back through the chain until the first
+/// address that isn't a trusted proxy.  Headers from untrusted peers are ignored, so clients
+/// can't just claim to be someone else.
+///
+/// Routes can then declare IP allowlists and denylists in CIDR notation.  Requests from
+/// addresses that aren't allowed get a `403 Forbidden` before the handler is ever called.
+///
+/// Handlers that care about the client IP can take a `ClientIp` argument, which is always the
+/// address computed above.
+fn my_api_with_ip_rules() -> Api {
+    Api::new()
+        .trusted_proxies(&["10.0.0.0/8"])
+        .alt(
+            alts![
+                path("user")
+                    .path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .deny_ips(&["192.0.2.0/24"]),
+                path("admin")
+                    .path("users")
+                    .ret(GET, "Vec<User>")
+                    .allow_ips(&["203.0.113.0/24", "2001:db8::/32"]),
+            ]
+        )
+}
+
+fn handler_admin_users(clientIp: ClientIp) -> Vec<User> {
+    todo!();
+}
+
+
+/// Since the server wiring is generated, applications need some way to hook into it without
+/// editing generated code.  `Server` takes a set of typed lifecycle hooks:
+///
+/// -   `on_start` is called once the listeners are bound, with the addresses being served.
+/// -   `on_route_registered` is called for every route as it is mounted, with the route's name,
+///     method, and path template.
+/// -   `on_request_rejected` is called when a request never reaches a handler, for instance
+///     because a capture didn't parse or the body wasn't valid JSON.  It gets the reason the
+///     request was rejected.
+/// -   `on_handler_error` is called whenever a handler returns an error.
+///
+/// Each hook takes a typed event struct instead of a bag of strings, so adding new fields later
+/// doesn't break existing hooks.  Hooks are only for observing what happens; they can't change
+/// the response.
+fn serve_my_api_with_hooks() {
+    Server::bind("[::]:8080")
+        .on_start(|event: StartEvent| println!("listening on {:?}", event.addrs))
+        .on_route_registered(|event: RouteRegisteredEvent| {
+            println!("mounted {} {} ({})", event.method, event.path_template, event.name)
+        })
+        .on_request_rejected(|event: RequestRejectedEvent| {
+            metrics::increment("rejected", &[("route", event.route_name), ("why", event.reason)])
+        })
+        .on_handler_error(|event: HandlerErrorEvent| {
+            log::error!("{} failed: {}", event.route_name, event.error)
+        })
+        .serve(my_api_server());
+}
+
+
+/// There are a few places that need to turn query parameters into a string that is stable:
+///
+/// -   the generated client, when building the URL for a request,
+/// -   the response cache from `.cached(ttl)`, when building a cache key,
+/// -   request signing, where both sides have to sign exactly the same bytes.
+///
+/// All of these should go through one canonical encoding, instead of each doing its own thing.
+/// The canonical encoding sorts parameters by key (keeping the relative order of repeated keys),
+/// percent-encodes everything except the RFC 3986 unreserved characters, always uses uppercase
+/// hex digits, and encodes spaces as `%20` rather than `+`.
+///
+/// This means the result doesn't depend on the order the query parameters were declared in the
+/// `Api`, or the order a client happened to send them in:
+///
+/// ```
+/// let a = CanonicalQuery::parse("sort=true&page=2");
+/// let b = CanonicalQuery::parse("page=2&sort=1");
+///
+/// // Values are also normalized using the declared type of each parameter.  This uses
+/// // `my_api_with_param_groups`, where `users_get` declares both `sort` and `page`.
+/// let api = my_api_with_param_groups();
+/// assert_eq!(a.normalize(&api, "users_get"), b.normalize(&api, "users_get"));
+/// assert_eq!(a.to_string(), "page=2&sort=true");
+/// ```
+fn canonical_users_get_query(sort: bool, page: u32) -> CanonicalQuery {
+    CanonicalQuery::new()
+        .push("sort", sort)
+        .push("page", page)
+}
+
+
+/// In real applications, the types on the wire usually aren't the same as the types used
+/// internally.  For instance, internally we might have a `DbUser` that comes straight from the
+/// database, while the API returns a `UserDto`.  Handlers end up full of code converting back and
+/// forth between the two.
+///
+/// A `#[derive(ApiDto)]` macro could generate these conversions.  The wire type names the domain
+/// type it converts from, and fields are matched up by name.  Field attributes handle the cases
+/// where they don't match:
+///
+/// -   `#[dto(rename = "...")]` maps a field to a differently-named domain field.
+/// -   `#[dto(with = "...")]` converts a field with a function.
+/// -   `#[dto(skip)]` on a wire field means it isn't read from the domain type at all.  The
+///     `From<DbUser>` conversion fills the wire field with `Default::default()`.
+///
+/// Domain fields with no matching wire field, like `password_hash` below, are just not sent.
+///
+/// The derive on `UserDto` generates `From<DbUser> for UserDto`.  If any field conversion can
+/// fail, it generates `TryFrom` instead, with an error naming the field that failed.  Adding
+/// `#[dto(both)]` also generates `From<UserDto> for DbUser`, and in that direction it is the
+/// domain fields with no wire field (`password_hash`) that get `Default::default()`.
+///
+/// Handlers can then convert the domain type with `.into()`, or `generate_server!` can do the
+/// conversion itself if the handler returns `DbUser` and the `Api` route returns `UserDto`.
+struct DbUser {
+    user_id: u32,
+    display_name: String,
+    password_hash: String,
+}
+
+#[derive(ApiDto)]
+#[dto(from = "DbUser")]
+struct UserDto {
+    #[dto(rename = "user_id", with = "UserId")]
+    id: UserId,
+    #[dto(rename = "display_name", with = "Name")]
+    name: Name,
+}
+
+fn my_api_with_dtos() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<UserDto>"),
+            ]
+        )
+}
+
+fn handler_users_get_db(sort: bool) -> Vec<UserDto> {
+    let db_users: Vec<DbUser> = todo!();
+    db_users.into_iter().map(Into::into).collect()
+}
+
+
+/// Routes can also carry example requests and responses.  These show up in the generated docs
+/// as-is, but they can also be turned into code snippets showing how to call the route from
+/// several languages.
+///
+/// For `user_create` below, the docs would have a tab for each language:
+///
+/// ```
+/// curl -X POST https://api.example.com/user/create/1 \
+///     -H 'Content-Type: application/json' \
+///     -d '"Alice"'
+/// ```
+///
+/// ```
+/// let user = client.user_create(UserId(1), Name("Alice".to_string()));
+/// ```
+///
+/// ```
+/// const user = await client.userCreate(1, "Alice");
+/// ```
+///
+/// ```
+/// user = client.user_create(1, "Alice")
+/// ```
+///
+/// The Rust, TypeScript, and Python snippets use the generated clients, so the snippets are
+/// always in sync with the client method names and argument orders.  All snippets are built from
+/// the same example, so they can't disagree with each other.
+fn my_api_with_examples() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .example(Example::new()
+                        .capture("id", json!(1))
+                        .body(json!("Alice"))
+                        .response(json!({ "id": 1, "name": "Alice" }))),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+generate_docs!(my_api_with_examples,
+    snippets = [Curl, Rust, TypeScript, Python]
+);
+
+
+/// Our mobile clients care a lot about how big responses are.  Routes can declare a budget for
+/// the size of their serialized payloads, and for how much time the generated wiring (extraction,
+/// deserialization, serialization) is allowed to add on top of the handler.
+///
+/// `generate_budget_tests!` creates one test per route that has a budget.  Each test serializes
+/// the route's examples and checks them against `.payload_budget(..)` (a `ByteSize`, so the unit
+/// is always explicit), and then runs the route's generated wiring against a no-op handler a few
+/// thousand times and checks the median against `.overhead_budget(..)`.  Running `cargo test` in
+/// CI then fails as soon as someone adds a big field to `User`:
+///
+/// ```
+/// ---- budget_users_get stdout ----
+/// users_get: example response is 14.2 KiB, but the payload budget is 8 KiB
+/// ```
+///
+/// Routes without examples can't be size-checked, so declaring a payload budget on a route
+/// without any examples is an error.
+fn my_api_with_budgets() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .overhead_budget(Duration::from_micros(50)),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .example(Example::new()
+                        .query("sort", json!(true))
+                        .response(json!([{ "id": 1, "name": "Alice" }])))
+                    .payload_budget(ByteSize::kib(8)),
+            ]
+        )
+}
+
+generate_budget_tests!(my_api_with_budgets);
+
+
+/// Our user store is replicated, so a read right after a write might not see the write yet.
+/// Instead of leaving this implicit, routes can declare how they take part in read-after-write
+/// consistency.
+///
+/// -   `.issues_consistency_token()` on a mutating route means the handler returns a
+///     `ConsistencyToken` alongside its normal response.  The generated server sends it in the
+///     `Consistency-Token` response header.
+///
+/// -   `.accepts_consistency_token()` on a read route means the handler can take an
+///     `Option<ConsistencyToken>` argument, filled in from the `Consistency-Token` request
+///     header.  The handler can use it to wait for (or route to) a replica that has caught up.
+///
+/// ```
+/// fn handler_user_create(userId: UserId, name: Name) -> (User, ConsistencyToken) {
+///     ...
+/// }
+///
+/// fn handler_users_get(token: Option<ConsistencyToken>, sort: bool) -> Vec<User> {
+///     ...
+/// }
+/// ```
+///
+/// The generated client can keep track of this automatically.  A `Session` remembers the most
+/// recent token it has seen from a write, and sends it with every following read:
+///
+/// ```
+/// let session = client.session();
+/// session.user_create(UserId(1), Name("Alice".to_string()));
+/// let users = session.users_get(true);  // guaranteed to include Alice
+/// ```
+///
+/// Both headers are documented on the routes that use them.
+fn my_api_with_consistency() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .issues_consistency_token(),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .accepts_consistency_token(),
+            ]
+        )
+}
+
+
+/// When generating clients for other languages, the generated source files on their own aren't
+/// enough to publish anything.  Someone still has to write a `package.json`, `pyproject.toml`,
+/// or `go.mod` by hand, and remember to bump the version in it.
+///
+/// Instead, the `Api` can carry some metadata about itself, and the foreign-language client
+/// generators can use it to write out the packaging files along with the client:
+///
+/// ```
+/// sdk/typescript/package.json
+/// sdk/typescript/src/index.ts
+/// sdk/python/pyproject.toml
+/// sdk/python/my_api_client/__init__.py
+/// sdk/go/go.mod
+/// sdk/go/client.go
+/// ```
+///
+/// The package version always comes from `.version(..)` on the `Api`, so publishing a new SDK is
+/// just bumping the `Api` version, regenerating, and running `npm publish` (or `twine upload`, or
+/// pushing a go tag).  Anything language-specific, like the npm package name, is given in the
+/// `Package` for that language.
+fn my_api_with_metadata() -> Api {
+    Api::new()
+        .title("My API")
+        .version("1.4.0")
+        .license("MIT")
+        .repository("https://github.com/example/my-api")
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+generate_foreign_clients!(my_api_with_metadata,
+    out_dir = "sdk",
+    packages = [
+        Package::typescript("@example/my-api-client"),
+        Package::python("my-api-client"),
+        Package::go("github.com/example/my-api-client-go"),
+    ]
+);
+
+
+/// When a request body fails to deserialize, serde's error message on its own is not very
+/// helpful for the person calling the API.  Something like `invalid type: integer, expected a
+/// string at line 1 column 27` doesn't say which field was wrong in a big nested body.
+///
+/// The generated server should track the path while deserializing (the same way the
+/// `serde_path_to_error` crate does), and return a structured error body instead:
+///
+/// ```
+/// HTTP/1.1 422 Unprocessable Entity
+/// Content-Type: application/json
+///
+/// {
+///     "error": "invalid_body",
+///     "pointer": "/users/3/name",
+///     "expected": "Name (a JSON string)",
+///     "found": "123",
+///     "message": "invalid type: integer `123`, expected a string"
+/// }
+/// ```
+///
+/// -   `pointer` is an RFC 6901 JSON pointer to the offending value.
+/// -   `expected` is the declared type from the `Api`, along with what it looks like in JSON.
+/// -   `found` is a snippet of the offending value, cut off after 64 characters so we never echo
+///     a whole huge body back.  Values of fields declared `.sensitive(..)` are never included.
+///
+/// Whether this is a 400 or a 422 can be picked on the `Api`.
+///
+/// The generated client uses the same tracking when decoding responses, so when the server
+/// sends back something the client doesn't expect, the `DecodeError` also has the pointer,
+/// expected type, and snippet, instead of just the serde message.
+fn my_api_with_decode_errors() -> Api {
+    Api::new()
+        .invalid_body_status(422)
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Since nothing about `generate_server!` is specific to a single web framework, the same
+/// handlers can be served over more than one transport at once.
+///
+/// Along with `my_api_server`, `generate_server!` can also create `my_api_handler_table`, which
+/// returns the handlers collected into a single `HandlerTable`.  This is shared between all the
+/// backends.  Each backend takes care of its own listener (an HTTP port, a NATS subject prefix,
+/// etc), but they all dispatch into the same handler table, and lifecycle hooks and layers on
+/// `MultiServer` apply to all of them.
+///
+/// This is useful when migrating between transports.  Both transports can be served from the
+/// same process while clients move over, without duplicating any handler code.
+///
+/// If one backend fails to start, none of them are started.  Shutting down the `MultiServer`
+/// drains all of the backends before returning.
+fn serve_my_api_multi() {
+    let handlers = my_api_handler_table();
+
+    MultiServer::new(handlers)
+        .backend(HttpBackend::new(Server::bind("[::]:8080")))
+        .backend(NatsBackend::new("nats://localhost:4222", "my_api.rpc"))
+        .serve();
+}
+
+
+/// A lot of list routes take the same set of query parameters.  Instead of repeating
+/// `.query("page", "u32").query("per_page", "u32").query("sort", "bool")` on every one of them,
+/// the parameters can be declared once as a struct and attached with `.params::<..>()`.
+///
+/// Each field of the struct becomes its own query parameter, so docs, OpenAPI, and the generated
+/// client all show `page`, `per_page`, and `sort` as separate parameters, exactly as if they had
+/// been declared one by one.  Doc comments on the fields become the parameter descriptions.
+///
+/// The handler gets the whole struct as a single argument:
+///
+/// ```
+/// fn handler_users_get(params: CommonListParams) -> Vec<User> {
+///     ...
+/// }
+/// ```
+///
+/// Parameter groups can be mixed with normal `.query(..)` parameters.  It is an error at
+/// generation time for a group to have a field with the same name as another parameter on the
+/// route.
+#[derive(ApiParams)]
+struct CommonListParams {
+    /// The page to return, starting from 1.
+    page: Option<u32>,
+    /// How many items to return per page.
+    per_page: Option<u32>,
+    /// Whether to sort the results.
+    sort: bool,
+}
+
+fn my_api_with_param_groups() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .params::<CommonListParams>()
+                    .ret(GET, "Vec<User>"),
+                path("deleted")
+                    .params::<CommonListParams>()
+                    .query("since", "Date")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// The `Api` can also know where it is deployed.  Each named environment has a base URL, and the
+/// generated client gets an `Env` enum with one variant per environment:
+///
+/// ```
+/// enum Env {
+///     Prod,
+///     Staging,
+///     Dev,
+/// }
+///
+/// impl Client {
+///     fn for_env(env: Env) -> Client {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// `Client::for_env(Env::Staging)` uses the staging base URL, unless the `MY_API_BASE_URL`
+/// environment variable is set, in which case that wins.  The name of the variable can be
+/// changed with `.base_url_env_var(..)`.  This lets tools default to the right server, while
+/// still being pointed at a local server when developing.
+///
+/// The environments and their URLs also show up in the generated docs (and as `servers` in
+/// OpenAPI).
+fn my_api_with_envs() -> Api {
+    Api::new()
+        .env("prod", "https://api.example.com")
+        .env("staging", "https://api.staging.example.com")
+        .env("dev", "http://localhost:8080")
+        .base_url_env_var("MY_API_BASE_URL")
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Handlers that share state can have race conditions that never show up in normal tests,
+/// since normal tests only send one request at a time.
+///
+/// `ConcurrencyTest` runs a set of typed requests against the generated server concurrently,
+/// without going through the network.  Like `shuttle`, it controls the scheduling of the
+/// handlers' tasks, tries many different interleavings, and replays the exact interleaving
+/// whenever one of them fails.  After each run it checks the invariants we declare.
+///
+/// Requests are built with the same typed methods as the generated client, so the test can't
+/// send a request that doesn't match the `Api`.
+///
+/// ```
+/// #[test]
+/// fn user_create_is_idempotent() {
+///     ConcurrencyTest::new(my_api_server())
+///         .iterations(1000)
+///         .concurrently(|client| {
+///             client.user_create(UserId(1), Name("Alice".to_string()));
+///             client.user_create(UserId(1), Name("Alice".to_string()));
+///             client.users_get(false);
+///         })
+///         .invariant("exactly one user with id 1", |client| {
+///             client.users_get(false).iter().filter(|u| u.id.0 == 1).count() == 1
+///         })
+///         .run();
+/// }
+/// ```
+///
+/// A failing run prints the seed and the order the requests were interleaved in, and setting
+/// `SAABANTO_CONCURRENCY_SEED` replays that exact schedule.
+fn concurrency_test_my_api() -> ConcurrencyTest {
+    ConcurrencyTest::new(my_api_server())
+}
+
+
+/// Our user data is sharded by region, and every user lives in exactly one region.  Routes can
+/// declare how to figure out which region a request belongs to, with a `RegionKey`.
+///
+/// On the server side, the generated server computes the region for every request before
+/// calling the handler, and passes it to a pluggable `RegionRouter`.  The router decides whether
+/// the request can be handled locally, or has to be forwarded to another region (or rejected
+/// with `421 Misdirected Request`).  Handlers never see requests for the wrong region.
+///
+/// ```
+/// trait RegionRouter {
+///     fn route(&self, region: &Region, request: &Request) -> RegionDecision;
+/// }
+/// ```
+///
+/// On the client side, the `Api` environments can have one base URL per region.  The generated
+/// client computes the same `RegionKey` from the method arguments, and sends the request straight
+/// to the right regional base URL.  Cross-region calls only happen when we explicitly ask for
+/// them with `client.in_region(..)`.
+///
+/// The docs for each route say which capture or header determines its region.
+fn my_api_with_regions() -> Api {
+    Api::new()
+        .env_region("prod", "us", "https://us.api.example.com")
+        .env_region("prod", "eu", "https://eu.api.example.com")
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .region_affinity(RegionKey::from_capture("id")),
+                path("get")
+                    .query("sort", "bool")
+                    .query("region", "Region")
+                    .ret(GET, "Vec<User>")
+                    .region_affinity(RegionKey::from_query("region")),
+            ]
+        )
+}
+
+
+/// Some of our API goes the other way: we send requests to our partners.  These can be declared
+/// in the `Api` too, so they are documented alongside everything else.
+///
+/// -   `.webhook(..)` on the `Api` declares a request we send to partners whenever something
+///     happens, independent of any particular route.
+/// -   `.callback(..)` on a route declares a request we send back to a URL the caller gave us in
+///     that route's request.
+///
+/// Besides Markdown docs, these should be emitted into the OpenAPI output properly.  Webhooks
+/// go in the top-level `webhooks` section (OpenAPI 3.1), and callbacks go in the `callbacks`
+/// section of the route they belong to, with the callback URL given as a runtime expression
+/// pointing at the body field that holds it:
+///
+/// ```
+/// "callbacks": {
+///     "export_finished": {
+///         "{$request.body#/callback_url}": {
+///             "post": { ... }
+///         }
+///     }
+/// }
+/// ```
+///
+/// This way partners can feed our OpenAPI spec to their own generators, and get webhook
+/// receivers along with the client.
+fn my_api_with_webhooks() -> Api {
+    Api::new()
+        .webhook(
+            webhook("user_created")
+                .body("user", "User")
+                .ret(POST, "()")
+        )
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("export")
+                    .body("request", "ExportRequest")
+                    .ret(POST, "ExportId")
+                    .callback(
+                        callback("export_finished", "callback_url")
+                            .body("result", "ExportResult")
+                            .ret(POST, "()")
+                    ),
+            ]
+        )
+}
+
+generate_openapi!(my_api_with_webhooks);
+
+
+/// What should a route returning `()` actually send?  Depending on the web framework, we might
+/// get `204 No Content`, `200` with a body of `null`, or `200` with a body of `{}`.  Consumers
+/// end up depending on whichever one they happened to see.
+///
+/// The `Api` should make this an explicit choice, with `EmptyResponse`:
+///
+/// -   `EmptyResponse::NoContent` sends `204 No Content` with no body.
+/// -   `EmptyResponse::Null` sends `200 OK` with a body of `null`.
+/// -   `EmptyResponse::EmptyObject` sends `200 OK` with a body of `{}`.
+///
+/// The default is set on the `Api`, and can be overridden per route.  Every backend has to
+/// follow it, instead of doing whatever the framework does by default.
+///
+/// The generated client decodes exactly the declared encoding.  For compatibility with servers
+/// that are still being migrated, `.accept_any_empty()` on the client also accepts the other two.
+///
+/// The docs and OpenAPI output show the actual status code and body for each of these routes,
+/// rather than a generic "returns nothing".
+fn my_api_with_empty_responses() -> Api {
+    Api::new()
+        .empty_response(EmptyResponse::NoContent)
+        .path("user")
+        .alt(
+            alts![
+                path("delete")
+                    .capture("id", "UserId")
+                    .ret(DELETE, "()"),
+                path("touch")
+                    .capture("id", "UserId")
+                    .ret(POST, "()")
+                    .empty_response(EmptyResponse::EmptyObject),
+            ]
+        )
+}
+
+
+/// The generated client should identify itself.  By default, it sends a `User-Agent` built from
+/// the client crate's name and version, the `Api` version it was generated from, and the target
+/// it was compiled for:
+///
+/// ```
+/// User-Agent: my-api-client/0.3.1 (api 1.4.0; x86_64-unknown-linux-gnu)
+/// ```
+///
+/// Optionally, it also sends telemetry headers describing the SDK:
+///
+/// ```
+/// X-Sdk-Name: my-api-client
+/// X-Sdk-Version: 0.3.1
+/// X-Sdk-Language: rust
+/// ```
+///
+/// The foreign-language clients send the same headers, with their own language and version.
+/// On the server side, this means we can tell which SDK versions are sending which traffic
+/// when debugging an incident.
+///
+/// Applications can add their own product name in front of the default `User-Agent`, replace it
+/// completely, or turn off the telemetry headers.
+fn my_api_client_user_agent() -> Client {
+    Client::builder("https://api.example.com")
+        .user_agent_prefix("billing-tool/2.0")
+        .telemetry_headers(false)
+        .build()
+}
+
+
+/// Some clients sit behind proxies that only let `GET` and `POST` through.  The `Api` can opt in
+/// to method overriding, so that these clients can still reach `PUT` and `DELETE` routes.
+///
+/// With `MethodOverride::Header`, a `POST` request with an `X-HTTP-Method-Override: DELETE`
+/// header is dispatched to the `DELETE` route of the same path.  With `MethodOverride::FormField`,
+/// a `_method` field in a form-encoded body does the same thing.
+///
+/// Only `POST` requests can be overridden, and only to the methods listed.  Overriding is applied
+/// before routing, so everything after that (auth, rate limits, logging) sees the overridden
+/// method.
+///
+/// This is off by default.  When it is turned on, the docs explain how to use it, and the
+/// generated client can be told to send overrides itself with `.use_method_override(true)`.
+fn my_api_with_method_override() -> Api {
+    Api::new()
+        .method_override(MethodOverride::Header, &[PUT, DELETE])
+        .path("user")
+        .alt(
+            alts![
+                path("update")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(PUT, "User"),
+                path("delete")
+                    .capture("id", "UserId")
+                    .ret(DELETE, "()"),
+            ]
+        )
+}
+
+
+/// Clients usually have a timeout, but the server has no idea what it is.  It can keep working
+/// on a request long after the client has given up on it.
+///
+/// The generated client sends its remaining timeout in a `Request-Deadline` header, as a number
+/// of milliseconds (like gRPC's `grpc-timeout`).  The generated server reads it, and handlers can
+/// take a `Deadline` argument to see how much time is left:
+///
+/// ```
+/// fn handler_users_get(deadline: Deadline, sort: bool) -> Vec<User> {
+///     if deadline.remaining() < Duration::from_millis(50) {
+///         // return something cheaper
+///     }
+///     ...
+/// }
+/// ```
+///
+/// When the deadline passes, the server stops waiting for the handler, drops its future, and
+/// returns `504 Gateway Timeout`.  The `Api` can cap the deadline a client is allowed to ask for,
+/// and give a default for requests without the header.
+///
+/// When a handler calls another service with a generated client, passing the `Deadline` to the
+/// client makes it send on the remaining budget, so the deadline carries all the way down.
+fn my_api_with_deadlines() -> Api {
+    Api::new()
+        .default_deadline(Duration::from_secs(10))
+        .max_deadline(Duration::from_secs(30))
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Besides the transport, the generated client can have middleware: things that wrap every call,
+/// and know which operation is being called.  One useful middleware is a circuit breaker.
+///
+/// The circuit breaker keeps separate state for every operation:
+///
+/// -   **Closed**: calls go through normally.  After `failure_threshold` consecutive failures
+///     (connection errors, timeouts, or 5xx responses), the breaker opens.
+/// -   **Open**: calls fail immediately with `ClientError::CircuitOpen`, without sending
+///     anything.  After `open_for` has passed, the breaker becomes half-open.
+/// -   **Half-open**: a single trial call is let through.  If it succeeds, the breaker closes
+///     again; if it fails, it goes back to open.
+///
+/// Since the state is per operation, one broken route doesn't stop calls to the rest of the API.
+///
+/// A fallback can be given for an operation, which is called instead of failing while the
+/// breaker is open.  It has the same return type as the client method, so the compiler checks
+/// that the fallback makes sense.
+fn my_api_client_circuit_breaker() -> Client {
+    Client::builder("https://api.example.com")
+        .middleware(
+            CircuitBreaker::new()
+                .failure_threshold(5)
+                .open_for(Duration::from_secs(30))
+                .fallback("users_get", |_sort: bool| -> Vec<User> { vec![] })
+        )
+        .build()
+}
+
+
+/// Routes can declare that they need an API key with `.auth(Auth::ApiKey(..))`, which says where
+/// the key goes (a header or a query parameter).  On its own, this just documents the
+/// requirement, and every team has to check the key themselves.
+///
+/// The server side can take care of this with a `KeyStore`:
+///
+/// ```
+/// trait KeyStore {
+///     /// Look up a key by its public id (the part before the `.`).
+///     fn lookup(&self, key_id: &str) -> Option<StoredKey>;
+/// }
+///
+/// struct StoredKey {
+///     secret_hash: Vec<u8>,
+///     owner: String,
+///     scopes: Vec<String>,
+///     expires_at: Option<SystemTime>,
+/// }
+/// ```
+///
+/// Keys look like `<key_id>.<secret>`.  The generated server looks up the key id, hashes the
+/// secret, and compares it against the stored hash in constant time.  Expired keys are rejected,
+/// and `.scope(..)` on a route rejects keys without that scope, both with `401`/`403` before the
+/// handler is called.  Handlers can take an `ApiKeyInfo` argument with the key's owner and
+/// scopes.
+///
+/// Rotation is handled by letting a key have a successor.  While both are valid, requests with
+/// the old key succeed but call the `on_deprecated_key_used` hook, so we can see who hasn't
+/// switched over yet.  `generate_api_key()` makes new keys in the right format.
+fn my_api_with_api_keys() -> Api {
+    Api::new()
+        .auth(Auth::ApiKey(KeyLocation::Header("X-Api-Key")))
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .scope("users:write"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .scope("users:read"),
+            ]
+        )
+}
+
+fn serve_my_api_api_keys() {
+    Server::bind("[::]:8080")
+        .key_store(PostgresKeyStore::new(db_pool()))
+        .on_deprecated_key_used(|key: &ApiKeyInfo| log::warn!("{} uses a rotated key", key.owner))
+        .serve(my_api_server());
+}
+
+
+/// With all the wiring generated by macros, it can be hard to tell what is actually being served.
+/// `Server::print_routes()` prints a table of everything that is mounted:
+///
+/// ```
+/// METHOD  PATH                 OPERATION    HANDLER              AUTH
+/// POST    /user/create/<id>    user_create  handler_user_create  api_key (users:write)
+/// GET     /user/get?sort       users_get    handler_users_get    api_key (users:read)
+/// ```
+///
+/// Columns are aligned to the longest entry, and routes are listed in the order they appear in
+/// the `Api`.  Calling `.print_routes_on_start()` prints the table once the listeners are bound,
+/// along with a short banner with the `Api` title, version, and the addresses being served.
+///
+/// The same information is available as data with `Server::route_table()`, for applications that
+/// want to log it in some structured format instead of printing it.
+fn serve_my_api_with_banner() {
+    Server::bind("[::]:8080")
+        .print_routes_on_start()
+        .serve(my_api_server());
+}
+
+
+/// Not every route should end up in every client.  Our public SDK shouldn't have methods for
+/// internal admin routes, even though they are defined in the same `Api`.
+///
+/// Routes can be given tags with `.tag(..)`, and `generate_client!` can be told which routes to
+/// include:
+///
+/// -   `include` takes glob patterns on operation names.  Only matching routes are generated.
+/// -   `exclude_tags` leaves out every route with one of the given tags.
+///
+/// Routes that are left out are not generated at all, so there is no way to call them from the
+/// public client by accident.  Types that are only used by left-out routes are also left out.
+///
+/// It is an error if an `include` pattern doesn't match any route, since that is almost always a
+/// typo.
+fn my_api_with_tags() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("user")
+                    .path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .name("user_create"),
+                path("user")
+                    .path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .name("users_get"),
+                path("admin")
+                    .path("users")
+                    .ret(GET, "Vec<User>")
+                    .name("admin_users")
+                    .tag("internal"),
+            ]
+        )
+}
+
+generate_client!(my_api_with_tags,
+    include = ["user_*", "users_*"],
+    exclude_tags = ["internal"]
+);
+
+
+/// Sometimes the same data is exposed both internally and publicly, but the public side should
+/// see less of it.  For instance, internal tools can see a user's email address, but the public
+/// API can't.
+///
+/// Instead of writing two handlers, a route can be mounted with a view.  `.view::<PublicUser>()`
+/// means the handler still returns `FullUser`, but the generated server converts it to
+/// `PublicUser` before serializing it.  The conversion is just `From<FullUser> for PublicUser`
+/// (which could be written with `#[derive(ApiDto)]`), so if `PublicUser` can't be built from a
+/// `FullUser`, this fails to compile rather than leaking fields at runtime.  Views also work on
+/// `Vec<FullUser>`, `Option<FullUser>`, etc.
+///
+/// Both `FullUser` and `PublicUser` are documented, each on the mount it is returned from, and
+/// the clients generated for each mount return the right type.
+struct FullUser {
+    id: UserId,
+    name: Name,
+    email: String,
+}
+
+#[derive(ApiDto)]
+#[dto(from = "FullUser")]
+struct PublicUser {
+    id: UserId,
+    name: Name,
+}
+
+fn my_api_with_views() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("internal")
+                    .path("user")
+                    .capture("id", "UserId")
+                    .ret(GET, "FullUser")
+                    .name("internal_user_get")
+                    .handler(handler_user_get),
+                path("user")
+                    .capture("id", "UserId")
+                    .ret(GET, "FullUser")
+                    .view::<PublicUser>()
+                    .name("user_get")
+                    .handler(handler_user_get),
+            ]
+        )
+}
+
+fn handler_user_get(userId: UserId) -> FullUser {
+    todo!();
+}
+
+
+/// Routes returning big lists have a long time-to-first-item.  The server has to build the whole
+/// `Vec<User>` and serialize it before sending anything, and the client has to wait for the
+/// closing `]` before it can decode anything.
+///
+/// `.stream_array_above(n)` on a route returning a `Vec<T>` changes this.  When the handler
+/// returns more than `n` elements, the generated server writes the array one element at a time
+/// with chunked transfer encoding, instead of serializing it into one buffer.  The bytes on the
+/// wire are still a normal JSON array, so clients that don't know about streaming still work.
+///
+/// Handlers can also return an `impl Stream<Item = T>` for these routes, so the whole list never
+/// has to be in memory at once.
+///
+/// The generated client gets an additional method for these routes, which decodes elements as
+/// they arrive:
+///
+/// ```
+/// impl Client {
+///     fn users_get(sort: bool) -> Vec<User> {
+///         ...
+///     }
+///     fn users_get_stream(sort: bool) -> impl Stream<Item = Result<User, ClientError>> {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// If the connection drops halfway through, the stream yields an error after the last complete
+/// element, instead of silently ending early.
+fn my_api_with_streaming_arrays() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .stream_array_above(1000),
+            ]
+        )
+}
+
+fn handler_users_get_streaming(sort: bool) -> impl Stream<Item = User> {
+    todo!();
+}
+
+
+/// The examples declared on routes are also good test data.  `generate_fixtures!` turns every
+/// example into a function in a `fixtures` module, returning the example as real Rust values
+/// instead of JSON:
+///
+/// ```
+/// mod fixtures {
+///     /// The example response for `user_create`.
+///     pub fn user() -> User {
+///         User { id: UserId(1), name: Name("Alice".to_string()) }
+///     }
+///
+///     /// The example request for `user_create`.
+///     pub fn user_create_request() -> (UserId, Name) {
+///         (UserId(1), Name("Alice".to_string()))
+///     }
+/// }
+/// ```
+///
+/// Since the fixtures are decoded from the same examples that appear in the docs, an example
+/// that doesn't match its type fails to compile the fixtures, instead of ending up in the docs.
+///
+/// The fixtures module can be used from both sides.  Server tests can call handlers with
+/// `fixtures::user_create_request()`, and client consumers can stub out the client with
+/// `fixtures::user()`.  Examples can be given names with `.example_named(..)`, which become the
+/// fixture function names when a route has more than one example.
+generate_fixtures!(my_api_with_examples);
+
+
+/// Handlers sometimes kick off background work, like sending a welcome email after creating a
+/// user.  When they do this with a plain `tokio::spawn`, the background task loses track of the
+/// request it came from, so its log lines don't have the request id and its outgoing calls don't
+/// carry the deadline or trace context.
+///
+/// Handlers can take a `RequestContext` argument, which holds everything the generated server
+/// knows about the current request: the request id, the `Deadline`, the authenticated principal,
+/// and the tracing span.  `RequestContext::spawn` spawns a task with the context captured, and
+/// restores it every time the task is polled:
+///
+/// ```
+/// fn handler_user_create(ctx: RequestContext, userId: UserId, name: Name) -> User {
+///     let email_name = name.clone();
+///     ctx.spawn(async move {
+///         // Logs from here still have the request id, and calls made with a generated
+///         // client still send the trace context.
+///         send_welcome_email(&email_name).await;
+///     });
+///     ...
+/// }
+/// ```
+///
+/// By default the spawned task *doesn't* inherit the deadline, since background work is usually
+/// supposed to outlive the request.  `ctx.spawn_within_deadline(..)` makes it inherit the deadline
+/// too, and cancels the task when the deadline passes.
+///
+/// `RequestContext::current()` gets the context from anywhere inside a handler or a task spawned
+/// this way, for code that is too deep to have it passed in.
+fn handler_user_create_with_context(ctx: RequestContext, userId: UserId, name: Name) -> User {
+    ctx.spawn(async move {
+        todo!();
+    });
+    todo!();
+}
+
+
+/// IP-based rate limits don't work well for authenticated APIs, since many users can share one
+/// IP and one user can have many.  Routes can instead declare a quota per authenticated
+/// principal (the API key owner, the mTLS principal, etc).
+///
+/// A `.quota(..)` gives a number of requests per window.  Quotas can be attached to single routes
+/// or to the whole `Api`, and a request has to fit in every quota that applies to it.  Counts are
+/// kept in a pluggable `QuotaStore`, so several server instances can share them:
+///
+/// ```
+/// trait QuotaStore {
+///     /// Count one request for this principal and quota, returning the remaining quota.
+///     fn hit(&self, principal: &Principal, quota: &QuotaId, window: Duration) -> QuotaState;
+/// }
+/// ```
+///
+/// Every response carries `X-RateLimit-Limit`, `X-RateLimit-Remaining`, and `X-RateLimit-Reset`.
+/// Requests over quota get `429 Too Many Requests` without calling the handler.
+///
+/// The generated client parses these headers, and every typed response has a `quota()` with
+/// the limit, the remaining count, and when it resets, so batch tools can slow down before they
+/// run out.
+fn my_api_with_quotas() -> Api {
+    Api::new()
+        .auth(Auth::ApiKey(KeyLocation::Header("X-Api-Key")))
+        .quota(Quota::per_principal(10_000, Duration::from_secs(3600)))
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .quota(Quota::per_principal(100, Duration::from_secs(60))),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+fn serve_my_api_quotas() {
+    Server::bind("[::]:8080")
+        .quota_store(InMemoryQuotaStore::new())
+        .serve(my_api_server());
+}
+
+
+/// The generated client and the generated server each have their own code for encoding and
+/// decoding captures, query parameters, and bodies.  If these ever disagree (say, the client
+/// percent-encodes a `/` in a capture but the server doesn't decode it), some requests silently
+/// come out different on the other side.
+///
+/// `generate_roundtrip_fuzz!` creates a fuzz target for every route.  Each target builds
+/// arbitrary values for the route's parameters and body with `arbitrary`, encodes them into a
+/// request using the client's code, decodes that request using the server's code, and asserts
+/// that the handler would have received exactly the values we started with.  Nothing goes over
+/// the network, so this runs fast enough for `cargo fuzz`:
+///
+/// ```
+/// $ cargo fuzz run roundtrip_user_create
+/// ```
+///
+/// The same targets can run as normal tests with a fixed number of iterations, so CI catches the
+/// obvious asymmetries without a fuzzing setup.  This is especially useful whenever codec options
+/// (like the canonical query encoding) change.
+generate_roundtrip_fuzz!(my_api,
+    test_iterations = 10_000
+);
+
+
+/// Not every route is equally stable.  Routes can be marked with a `Stability` level:
+///
+/// -   `Stability::Stable` is the default.
+/// -   `Stability::Beta` routes work normally, but their shape might still change.
+/// -   `Stability::Experimental` routes can change or go away at any time.
+///
+/// The stability level shows up everywhere the route does.  The docs show a badge next to beta
+/// and experimental routes.  Client methods for them get a doc note, and are behind a cargo
+/// feature (`beta` or `experimental`) shown with `#[doc(cfg(..))]`, so using them is a conscious
+/// choice.
+///
+/// The `Api` can also require an opt-in header for experimental routes.  Requests without
+/// `Api-Experimental: true` get `404 Not Found`, as though the route didn't exist.  The generated
+/// client sends the header automatically when the `experimental` feature is on.
+///
+/// When generating foreign-language SDKs, experimental routes are left out by default, and
+/// `include_stability = [Beta, Experimental]` puts them back.
+fn my_api_with_stability() -> Api {
+    Api::new()
+        .require_experimental_header(true)
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("search")
+                    .query("q", "String")
+                    .ret(GET, "Vec<User>")
+                    .stability(Stability::Beta),
+                path("recommend")
+                    .capture("id", "UserId")
+                    .ret(GET, "Vec<User>")
+                    .stability(Stability::Experimental),
+            ]
+        )
+}
+
+
+/// Logging and tracing every request is fine for most routes, but on our busiest routes it
+/// drowns the collector.  Routes can declare how they should be sampled, and the logging and
+/// tracing layers of the generated server follow it.
+///
+/// A `Sampling` has separate rates for successful and failed requests, because failures are
+/// almost always the interesting ones.  It can also cap the number of traces per second, so a
+/// traffic spike doesn't turn into a tracing spike.
+///
+/// The sampling decision is made once, before the handler is called, and is stored in the
+/// `RequestContext`.  This way a request is either traced all the way through or not at all,
+/// including background tasks spawned from it.  A request that turns out to fail is always
+/// logged, even if it wasn't sampled for success.
+///
+/// If the incoming request already has a sampled trace context, that always wins, so traces
+/// that start in another service don't get cut in half here.
+fn my_api_with_sampling() -> Api {
+    Api::new()
+        .sampling(Sampling::all())
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .sampling(
+                        Sampling::new()
+                            .log_successes(0.01)
+                            .log_errors(1.0)
+                            .max_traces_per_second(10)
+                    ),
+            ]
+        )
+}
+
+
+/// Some fields are sensitive enough that they shouldn't be readable by anyone who happens to see
+/// the response, like a logging proxy.  Schema fields can be marked `#[field(encrypted)]`.
+///
+/// The encryption itself is pluggable with a `FieldCipher`:
+///
+/// ```
+/// trait FieldCipher {
+///     fn encrypt(&self, field: &FieldPath, plaintext: &[u8]) -> Vec<u8>;
+///     fn decrypt(&self, field: &FieldPath, ciphertext: &[u8]) -> Result<Vec<u8>, CipherError>;
+/// }
+/// ```
+///
+/// When serializing a response, the generated server encrypts these fields and sends them as
+/// base64 strings.  Handlers always work with the plaintext values, and never have to call the
+/// cipher themselves.
+///
+/// Routes that are allowed to see the plaintext are marked with `.decrypts_fields()`.  The
+/// generated client for those routes gets a cipher of its own, and decrypts the fields after
+/// decoding.  Everywhere else, the client sees an `Encrypted<T>` it can pass around but not look
+/// inside.
+///
+/// The docs and OpenAPI output mark encrypted fields as protected (`format: encrypted`), so it is
+/// clear why they look like random strings.
+#[derive(ApiSchema)]
+struct UserProfile {
+    id: UserId,
+    name: Name,
+    #[field(encrypted)]
+    tax_id: String,
+}
+
+fn serve_my_api_field_encryption() {
+    Server::bind("[::]:8080")
+        .field_cipher(AesGcmFieldCipher::from_env("MY_API_FIELD_KEY"))
+        .serve(my_api_server());
+}
+
+
+/// `generate_docs!` can also produce HTML.  With `try_it = true`, every route in the HTML docs
+/// gets a small console: a form with one input per capture, query parameter, and body (pre-filled
+/// from the route's examples), a "Send" button, and a panel showing the actual response.
+///
+/// The whole thing is a single self-contained HTML file, with all the JavaScript and CSS inlined
+/// and no requests to CDNs, so it still works when emailed to a partner or opened offline.
+///
+/// At the top of the page there is a box for the target base URL (a dropdown of the `Api`
+/// environments, plus a free-form option), and an input for credentials matching the declared
+/// `Auth` of the `Api`.  Credentials are only ever kept in the page's memory, never in local
+/// storage or the URL.
+///
+/// Requests are sent straight from the browser with `fetch`, so the target server has to allow
+/// the docs' origin with CORS.  The docs say so when a request fails for that reason, instead of
+/// just showing a network error.
+generate_docs!(my_api_with_envs,
+    format = Html,
+    try_it = true
+);
+
+
+/// For small JSON responses, allocating a fresh `Vec<u8>` for every response is a noticeable part
+/// of the cost of the generated wiring.
+///
+/// Routes marked with `.pooled_buffers()` serialize into a buffer borrowed from a per-worker pool
+/// instead.  After the response is written, the buffer is cleared and returned to the pool, so
+/// steady-state traffic doesn't allocate for serialization at all.  Buffers that grew past
+/// `max_pooled_size` are dropped instead of being returned, so one huge response doesn't pin
+/// megabytes of memory forever.
+///
+/// This comes with a set of criterion benchmarks (`cargo bench --bench buffers`), which run the
+/// `users_get` wiring with and without pooling and count allocations with a counting global
+/// allocator.  The output would look something like this:
+///
+/// ```
+/// users_get/fresh_buffer     time: 1.84 µs   allocs/iter: 3
+/// users_get/pooled_buffer    time: 1.52 µs   allocs/iter: 1
+/// ```
+fn my_api_with_pooled_buffers() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .pooled_buffers(),
+            ]
+        )
+}
+
+fn serve_my_api_pooled_buffers() {
+    Server::bind("[::]:8080")
+        .buffer_pool(BufferPool::per_worker().max_pooled_size(64 * 1024))
+        .serve(my_api_server());
+}
+
+
+/// With the tower backend, the function created by `generate_server!` returns a
+/// `tower::Service`, and nothing about that service cares which HTTP version the request came in
+/// on.  So an experimental HTTP/3 listener (using `quinn` and `h3`) can serve the same service.
+///
+/// HTTP/3 runs over QUIC, which needs TLS, so `serve_h3` takes the same `Tls` config as the normal
+/// TLS listener.  It listens on UDP, usually on the same port number as the TCP listener.
+///
+/// Clients only try HTTP/3 once they know the server supports it.  When HTTP/3 is enabled, the
+/// HTTP/1.1 and HTTP/2 listeners add an `Alt-Svc` header to every response advertising it:
+///
+/// ```
+/// Alt-Svc: h3=":8443"; ma=86400
+/// ```
+///
+/// This is behind the `http3` cargo feature, since `h3` itself is still experimental.
+fn serve_my_api_http3() {
+    let tls = Tls::from_pem_files("cert.pem", "key.pem");
+
+    Server::bind("[::]:8443")
+        .tls(tls.clone())
+        .serve_h3("[::]:8443", tls)
+        .advertise_alt_svc(Duration::from_secs(86400))
+        .serve(my_api_server());
+}
+
+
+/// CORS can be declared on the whole `Api` with `.cors(..)`.  Some routes need something
+/// different from the rest, like a public widget endpoint that any site can call while the rest
+/// of the API only allows our own frontend.
+///
+/// `.cors(..)` can also be given on a group of routes or on a single route, and the most specific
+/// one wins.  The overrides are merged field by field, so a route only has to say what is
+/// different about it.
+///
+/// Some combinations don't make sense, and are reported when the server, client, or docs are
+/// generated instead of showing up as confusing browser errors:
+///
+/// -   allowing credentials together with a `*` origin,
+/// -   a route override allowing a method the route doesn't respond to,
+/// -   an override on a group that every route in the group overrides again, so it never applies.
+///
+/// The docs show the effective CORS policy of every route.
+fn my_api_with_cors() -> Api {
+    Api::new()
+        .cors(
+            Cors::new()
+                .allow_origins(&["https://app.example.com"])
+                .allow_credentials(true)
+        )
+        .alt(
+            alts![
+                path("user")
+                    .path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+                path("widget")
+                    .path("users")
+                    .ret(GET, "Vec<PublicUser>")
+                    .cors(
+                        Cors::new()
+                            .allow_any_origin()
+                            .allow_credentials(false)
+                    ),
+            ]
+        )
+}
+
+
+/// When a capture doesn't parse, like `/user/create/abc` when `id` is a `UserId(u32)`, what
+/// should the server return?  `400 Bad Request` is the most helpful, since it tells the caller
+/// what they did wrong.  But for some resources, a `400` gives away the format of their ids,
+/// and the difference between a `400` for a malformed id and a `404` for a missing one lets an
+/// attacker probe which ids are well-formed.  Our security team wants malformed ids on those
+/// routes to look exactly like an id that doesn't exist: `404 Not Found`, with the same body.
+///
+/// `InvalidCapture` picks between the two.  It can be set on the whole `Api`, and overridden on
+/// a single route:
+///
+/// -   `InvalidCapture::BadRequest` returns `400`, with a body saying which capture was invalid.
+/// -   `InvalidCapture::NotFound` returns `404`, with the same body as any other unmatched route.
+///
+/// The OpenAPI output lists whichever response a route actually gives for invalid captures.
+fn my_api_with_invalid_capture_policy() -> Api {
+    Api::new()
+        .invalid_capture(InvalidCapture::BadRequest)
+        .alt(
+            alts![
+                path("user")
+                    .path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("invoice")
+                    .capture("id", "InvoiceId")
+                    .ret(GET, "Invoice")
+                    .invalid_capture(InvalidCapture::NotFound),
+            ]
+        )
+}
+
+
+/// When a backend has a long latency tail, a slow request is usually slow because of where it
+/// landed, not because of what it asked for.  Sending the same request a second time often gets
+/// an answer sooner.
+///
+/// The `Hedging` client middleware does this for idempotent GET routes.  If the first attempt
+/// hasn't answered within the threshold, it sends a second attempt without cancelling the first,
+/// then takes whichever response comes back first and cancels the other one.
+///
+/// Hedging is only ever applied to `GET` routes, and to routes explicitly declared
+/// `.idempotent()`.  Trying to enable it for a route that is neither is an error when the client
+/// is built.
+///
+/// To keep hedging from doubling the load when the backend is slow across the board, at most
+/// `max_hedged_percent` of requests get a second attempt.  Hedged attempts carry a
+/// `Hedged-Attempt: 2` header, so the server side can tell them apart in metrics.
+fn my_api_client_hedging() -> Client {
+    Client::builder("https://api.example.com")
+        .middleware(
+            Hedging::new()
+                .operation("users_get", Duration::from_millis(50))
+                .max_hedged_percent(10)
+        )
+        .build()
+}
+
+
+/// For browser-facing routes, handlers often need a little bit of state that lives between
+/// requests, like a shopping cart.  `.session::<SessionData>()` gives handlers a typed session.
+///
+/// The handler takes a `Session<SessionData>` argument.  It derefs to `SessionData`, and can be
+/// mutated in place:
+///
+/// ```
+/// fn handler_cart_add(mut session: Session<CartSession>, item: ItemId) -> Cart {
+///     session.items.push(item);
+///     ...
+/// }
+/// ```
+///
+/// After the handler returns, the generated server saves the session if it was changed, and sets
+/// the session cookie on the response.  If the handler returns an error, changes are thrown away,
+/// so a failed request never leaves a half-updated session behind.  `session.destroy()` removes
+/// the session entirely, and `session.regenerate_id()` gives it a new id (which should be done on
+/// login).
+///
+/// Where sessions are stored is pluggable with `SessionStore`.  The built-in
+/// `SignedCookieStore` keeps the whole session in a signed cookie, and a Redis store can
+/// implement the same trait and keep only the id in the cookie.
+#[derive(Default, Serialize, Deserialize)]
+struct CartSession {
+    items: Vec<ItemId>,
+}
+
+fn my_api_with_sessions() -> Api {
+    Api::new()
+        .path("cart")
+        .alt(
+            alts![
+                path("add")
+                    .session::<CartSession>()
+                    .body("item", "ItemId")
+                    .ret(POST, "Cart"),
+            ]
+        )
+}
+
+fn serve_my_api_sessions() {
+    Server::bind("[::]:8080")
+        .session_store(SignedCookieStore::new("session", session_key()))
+        .serve(my_api_server());
+}
+
+
+/// Fields get deprecated too, not just routes.  Schema fields can use the normal
+/// `#[deprecated(since = "...", note = "...")]` attribute, and it is carried through everywhere:
+///
+/// -   The OpenAPI output marks the field `deprecated: true`, with the note in its description.
+/// -   The generated Rust client keeps the `#[deprecated]` attribute, so using the field is a
+///     compiler warning.  The TypeScript client gets a `@deprecated` JSDoc tag, and the Python
+///     client a `warnings.warn` in the property getter.
+/// -   The docs show the field struck through, with the version it was deprecated in.
+///
+/// For response fields, the server can report whenever a handler actually fills one in, with
+/// `.on_deprecated_field_populated(..)`.  A field counts as populated when it is serialized with
+/// a value, so an `Option` field left as `None` (and skipped) doesn't count.  The event has the
+/// route, the field, the principal, and the SDK name and version from the telemetry headers, so
+/// we can see which consumers are still being sent the old field before it is removed.
+///
+/// For request bodies, `.on_deprecated_field_sent(..)` does the same for clients that still send
+/// a deprecated field.
+#[derive(ApiSchema)]
+struct UserResponse {
+    id: UserId,
+    name: Name,
+    #[deprecated(since = "1.3.0", note = "use `name` instead")]
+    username: Option<String>,
+}
+
+fn serve_my_api_deprecated_fields() {
+    Server::bind("[::]:8080")
+        .on_deprecated_field_populated(|event: DeprecatedFieldEvent| {
+            log::info!(
+                "{} populated {} for {} ({} {})",
+                event.route_name, event.field, event.principal, event.sdk_name, event.sdk_version
+            )
+        })
+        .serve(my_api_server());
+}
+
+
+/// What the server does with a body that has no `Content-Type`, or the wrong one, currently
+/// depends on which web framework is underneath.  Some reject it, some assume JSON, and some
+/// try to guess.  The `Api` should decide this instead, with `ContentTypePolicy`:
+///
+/// -   `ContentTypePolicy::Strict` rejects anything other than `application/json` (or a
+///     `+json` suffix) with `415 Unsupported Media Type`.  This is the default.
+/// -   `ContentTypePolicy::AssumeJson` treats a missing `Content-Type` as JSON, but still rejects
+///     one that is wrong.
+/// -   `ContentTypePolicy::Sniff` looks at the first non-whitespace byte of the body, and treats
+///     it as JSON if it looks like JSON.  This is only meant for legacy clients.
+///
+/// The policy can be set on the `Api` and overridden per route, and the docs say which policy
+/// each route uses.
+fn my_api_with_content_type_policy() -> Api {
+    Api::new()
+        .content_type_policy(ContentTypePolicy::Strict)
+        .alt(
+            alts![
+                path("user")
+                    .path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("legacy")
+                    .path("user")
+                    .path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .content_type_policy(ContentTypePolicy::Sniff),
+            ]
+        )
+}
+
+
+/// Some routes are neither a single request and response, nor a websocket.  For our log
+/// forwarding endpoint, the client streams log lines up while the server streams acknowledgements
+/// back, over the same HTTP/2 request.
+///
+/// `.duplex(..)` declares a route like this, with the type of each message going up and down.
+/// Messages are sent as newline-delimited JSON in both directions.  The handler receives the
+/// incoming messages as a stream and returns the outgoing ones as a stream, and both run at the
+/// same time:
+///
+/// ```
+/// fn handler_logs_forward(incoming: impl Stream<Item = LogLine>) -> impl Stream<Item = LogAck> {
+///     ...
+/// }
+/// ```
+///
+/// The generated client method returns a sender and a receiver:
+///
+/// ```
+/// let (mut tx, mut rx) = client.logs_forward();
+/// tx.send(line).await?;
+/// let ack = rx.next().await;
+/// ```
+///
+/// Full duplex needs HTTP/2.  Backends that can't do it refuse to mount the route at startup.
+fn my_api_with_duplex() -> Api {
+    Api::new()
+        .path("logs")
+        .alt(
+            alts![
+                path("forward")
+                    .duplex(POST, "LogLine", "LogAck"),
+            ]
+        )
+}
+
+
+/// Firmware can't pull in an async HTTP client, but it can still talk to our API.  With
+/// `mode = SansIo`, `generate_client!` doesn't generate a client that sends requests at all.
+/// It generates functions that build requests and parse responses, and leaves sending them to
+/// whatever transport the device already has.  These go in their own module, named with
+/// `module`, so they don't clash with the normal `Client` generated for `my_api`:
+///
+/// ```
+/// mod my_api_no_std {
+///     pub fn user_create_request(id: UserId, name: &Name) -> http::Request<Vec<u8>> {
+///         ...
+///     }
+///     pub fn user_create_response(resp: http::Response<&[u8]>) -> Result<User, DecodeError> {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// The generated code only depends on `http`, `serde`, and `serde-json-core`, all with default
+/// features off, so it builds with `#![no_std]` and `alloc`.  Only the response parsing needs a
+/// buffer, and that is given by the caller.
+generate_client!(my_api,
+    mode = SansIo,
+    no_std = true,
+    module = my_api_no_std,
+    client_alts![
+        "user_create",
+        "users_get"
+    ]
+);
+
+
+/// Request signing proves a request came from the right client, but not that it is being seen
+/// for the first time.  Someone who captures a signed request can send it again.
+///
+/// `.nonce_protected(window)` on a route requires every request to carry a `Request-Nonce`
+/// header, and a `Request-Timestamp` header covered by the signature.  The server rejects the
+/// request with `401` if:
+///
+/// -   the timestamp is further than `window` away from the server's clock, or
+/// -   the same nonce has already been seen within `window`.
+///
+/// Seen nonces are kept in a pluggable `NonceStore`, and only need to be kept for `window`,
+/// since anything older is rejected by its timestamp anyway.  The generated client makes up a
+/// random nonce for each request automatically.
+fn my_api_with_nonces() -> Api {
+    Api::new()
+        .path("payment")
+        .alt(
+            alts![
+                path("create")
+                    .body("payment", "Payment")
+                    .ret(POST, "PaymentId")
+                    .signed(Signing::HmacSha256)
+                    .nonce_protected(Duration::from_secs(300)),
+            ]
+        )
+}
+
+fn serve_my_api_nonces() {
+    Server::bind("[::]:8080")
+        .nonce_store(InMemoryNonceStore::new())
+        .serve(my_api_server());
+}
+
+
+/// Adding a variant to an enum in a response is a breaking change for every client that decodes
+/// enums strictly, even though nothing about the old variants changed.
+///
+/// Enums can be marked `#[non_exhaustive_wire]` to say the server is allowed to add variants.
+/// The generated clients then decode variants they don't know about into an extra `Unknown`
+/// case holding the raw value, instead of failing the whole response:
+///
+/// ```
+/// enum UserStatus {
+///     Active,
+///     Suspended,
+///     Unknown(String),
+/// }
+/// ```
+///
+/// The TypeScript client gets `"active" | "suspended" | (string & {})`, and the Python client
+/// keeps unknown values as plain strings.  In all clients, `Unknown` can only come from decoding,
+/// and sending one back to the server is an error.
+///
+/// Since adding a variant to a `#[non_exhaustive_wire]` enum is safe, the API diff tool reports
+/// it as a compatible change.  Adding one to an enum without the attribute is still reported as
+/// breaking.
+#[derive(ApiSchema)]
+#[non_exhaustive_wire]
+enum UserStatus {
+    Active,
+    Suspended,
+}
+
+
+/// Serialization settings (how dates are formatted, how many digits floats get, how fields are
+/// renamed) are normally set once for the whole `Api`.  Sometimes one route has to be different,
+/// usually because an old consumer depends on its exact format.
+///
+/// `.serde_scope(..)` gives a route its own `SerdeSettings`.  Only the settings that are given
+/// are changed, and everything else comes from the `Api`.  The settings apply to the route's
+/// body, its response, and everything nested in them, but not to any other route, even ones that
+/// use the same types.
+///
+/// The generated client uses the same settings for that route's method, and the docs and OpenAPI
+/// output show that route's schemas with the overridden formats.  Since a type can then be
+/// serialized differently by different routes, the OpenAPI output gets a separate schema
+/// (`User_legacy_users_get`) for each different combination.
+fn my_api_with_serde_scopes() -> Api {
+    Api::new()
+        .serde_settings(
+            SerdeSettings::new()
+                .dates(DateFormat::Rfc3339)
+                .rename_fields(RenameRule::CamelCase)
+        )
+        .alt(
+            alts![
+                path("user")
+                    .path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+                path("legacy")
+                    .path("users")
+                    .ret(GET, "Vec<User>")
+                    .name("legacy_users_get")
+                    .serde_scope(
+                        SerdeSettings::new()
+                            .dates(DateFormat::UnixSeconds)
+                            .rename_fields(RenameRule::SnakeCase)
+                            .float_precision(2)
+                    ),
+            ]
+        )
+}
+
+
+/// Before switching a route over to a rewritten handler, it is nice to see how the new handler
+/// behaves on real production traffic.  A `MirrorLayer` sends a copy of a percentage of requests
+/// on selected routes to a second target, and throws the responses away.
+///
+/// The mirror target can either be another handler with the same signature (for rewrites within
+/// the same process) or an upstream URL (for a new deployment).  Mirroring happens in a
+/// background task after the real response has been sent, so the mirror can never slow down or
+/// break the real request.  If the mirror falls behind, mirrored requests are dropped rather than
+/// queued.
+///
+/// Only read routes (`GET`, or routes marked `.read_only()`) may be mirrored, whichever kind of
+/// target is used.  Idempotent isn't enough, since an idempotent `PUT` still changes things, and
+/// an in-process handler is the target most likely to share a database with the real one.
+/// Mirroring any other route needs `.allow_mutating(..)` for that route, which is a statement that
+/// the target has been set up to have no real side effects.  Mirrored requests to an upstream
+/// also carry a `Mirrored: true` header, so the shadow deployment can check for itself.
+///
+/// With `.compare()`, the layer also compares the mirrored response to the real one, and counts
+/// mismatches per route in metrics.
+fn serve_my_api_mirroring() {
+    Server::bind("[::]:8080")
+        .layer(
+            MirrorLayer::new()
+                .route("users_get", MirrorTarget::handler(handler_users_get_v2), 5)
+                .route("user_create", MirrorTarget::upstream("http://shadow:8080"), 1)
+                .allow_mutating("user_create")
+                .compare()
+        )
+        .serve(my_api_server());
+}
+
+fn handler_users_get_v2(sort: bool) -> Vec<User> {
+    todo!();
+}
+
+
+/// Each backend (Rocket, Axum, Actix, tower) has its own code translating the generated server
+/// into that framework.  It is easy for them to quietly drift apart, for instance in what
+/// happens with a trailing slash, or what the error body looks like when a query parameter is
+/// missing.
+///
+/// The conformance suite is a single set of test cases that every backend has to pass.  It uses
+/// a fixed test `Api` covering the edge cases, and each case is a raw HTTP request along with the
+/// exact status, headers, and body the response has to have:
+///
+/// -   routing: trailing slashes, percent-encoded slashes in captures, overlapping paths,
+///     `HEAD` on `GET` routes, `405` vs `404`,
+/// -   extraction: missing, repeated, and unparseable captures and query parameters,
+/// -   bodies: wrong `Content-Type`, invalid JSON, bodies over the size limit,
+/// -   responses: empty responses, error bodies, `charset` on `Content-Type`.
+///
+/// Each backend just needs to implement `ConformanceBackend`, which starts the test `Api` on a
+/// local port, and call `conformance_tests!`:
+///
+/// ```
+/// struct AxumConformance;
+///
+/// impl ConformanceBackend for AxumConformance {
+///     fn start(api: Api, handlers: HandlerTable) -> SocketAddr {
+///         ...
+///     }
+/// }
+///
+/// conformance_tests!(AxumConformance);
+/// ```
+///
+/// A new backend isn't considered supported until the whole suite passes.
+conformance_tests!(AxumConformance);
+
+
+/// On hot paths, decoding a response into owned types allocates a `String` for every string
+/// field.  For routes where that matters, the generated client can have a borrowed variant of the
+/// method, which deserializes into types that borrow from the response buffer:
+///
+/// ```
+/// struct UserRef<'a> {
+///     id: UserId,
+///     name: Cow<'a, str>,
+/// }
+///
+/// impl Client {
+///     fn users_get_borrowed(sort: bool) -> BorrowedResponse<UsersGetBorrowed> {
+///         ...
+///     }
+/// }
+///
+/// impl BorrowedResponse<UsersGetBorrowed> {
+///     fn get(&self) -> Result<Vec<UserRef<'_>>, DecodeError> {
+///         ...
+///     }
+/// }
+/// ```
+///
+/// `BorrowedResponse` owns the response buffer.  `get()` decodes the body with every borrow tied
+/// to the `BorrowedResponse` itself, so the borrowed values can't outlive the buffer:
+///
+/// ```
+/// let resp = client.users_get_borrowed(true)?;
+/// for user in resp.get()? {
+///     ...
+/// }
+/// ```
+///
+/// The borrowed types are generated from the owned ones, with `String` turned into
+/// `Cow<'a, str>`.  Strings without escapes borrow straight from the buffer, and only strings with
+/// escapes in them have to be unescaped into an owned copy.  Byte fields stay `Vec<u8>`, since
+/// their JSON form (base64 or an array of numbers) can never be borrowed as-is.  Borrowed types
+/// are only generated for routes that ask for them with `.borrowed_client()`, since they make the
+/// client bigger.
+fn my_api_with_borrowed_client() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .borrowed_client(),
+            ]
+        )
+}
+
+
+/// Since an `Api` is just a normal Rust value, applications should be able to look inside it
+/// too, not only the macros.  `Api::routes()` returns an iterator over every route, flattened out
+/// of all the nested `path` and `alt` calls:
+///
+/// ```
+/// struct RouteInfo<'a> {
+///     name: &'a str,
+///     method: Method,
+///     path_template: String,       // e.g. "/user/create/<id>"
+///     params: Vec<ParamInfo<'a>>,  // captures and query parameters, with their types
+///     body: Option<TypeInfo<'a>>,
+///     returns: TypeInfo<'a>,
+///     tags: &'a [String],
+///     auth: Option<&'a Auth>,
+/// }
+/// ```
+///
+/// Routes come out in the order they were declared.  This is enough to build things like an
+/// admin menu, a permissions matrix, or gateway configuration, without parsing the generated
+/// OpenAPI output back in.
+///
+/// `RouteInfo` is `#[non_exhaustive]`, so adding more fields to it later isn't a breaking change.
+fn print_permissions_matrix() {
+    for route in my_api_with_api_keys().routes() {
+        println!("{:<12} {:<6} {}", route.name, route.method, route.path_template);
+    }
+}
+
+
+/// Our edge gateways have their own route configuration, which has to be kept in sync with the
+/// `Api` by hand.  Exporters can generate it from `Api::routes()` instead:
+///
+/// -   `NginxExporter` writes `location` blocks, one per path template, with the allowed methods,
+///     `proxy_read_timeout` from the route's deadline, and `limit_req` from its rate limit.
+/// -   `EnvoyExporter` writes a route table for an HTTP connection manager, with per-route
+///     timeouts and local rate limits.
+/// -   `KongExporter` writes Kong declarative config, with a route per operation and the
+///     `rate-limiting` plugin where needed.
+///
+/// Captures are turned into each gateway's own path matching syntax (regexes for nginx and
+/// Envoy, `~` paths for Kong).  Settings that a gateway can't express are reported as warnings,
+/// instead of being silently left out.
+///
+/// ```
+/// location ~ ^/user/create/[^/]+$ {
+///     limit_except POST { deny all; }
+///     proxy_read_timeout 10s;
+///     proxy_pass http://my_api_upstream;
+/// }
+/// ```
+fn export_gateway_configs() {
+    let api = my_api_with_deadlines();
+    NginxExporter::new("my_api_upstream").write(&api, "gateway/nginx.conf");
+    EnvoyExporter::new("my_api_cluster").write(&api, "gateway/envoy-routes.yaml");
+    KongExporter::new("my-api-service").write(&api, "gateway/kong.yaml");
+}
+
+
+/// Along the same lines, the Kubernetes manifests routing traffic to our service can be
+/// generated too.  `KubernetesExporter` writes either a classic `Ingress`, or a Gateway API
+/// `HTTPRoute`, for the `Api`'s routes.
+///
+/// The host, service name, and port are parameters, since they differ per deployment, while the
+/// paths come from the `Api`.  Where the gateway supports it, a match is emitted for each route
+/// with its method, otherwise routes are merged into `PathPrefix` matches on the longest shared
+/// prefix, so the manifest stays short.
+///
+/// ```
+/// apiVersion: gateway.networking.k8s.io/v1
+/// kind: HTTPRoute
+/// metadata:
+///   name: my-api
+/// spec:
+///   hostnames: ["api.example.com"]
+///   rules:
+///     - matches:
+///         - path: { type: PathPrefix, value: /user/create }
+///           method: POST
+///       backendRefs:
+///         - name: my-api
+///           port: 8080
+/// ```
+///
+/// Terraform users can wrap the generated YAML in a `kubernetes_manifest` resource.  The manifest
+/// only covers routing, so deployments and services still come from wherever they live now.
+fn export_kubernetes_manifests() {
+    KubernetesExporter::http_route("my-api")
+        .host("api.example.com")
+        .service("my-api", 8080)
+        .write(&my_api(), "deploy/httproute.yaml");
+}
+
+
+/// Routes that return files can say so with `.ret_file(GET)`.  The handler returns a `FileBody`,
+/// which is a reader that can seek, along with its length and content type:
+///
+/// ```
+/// fn handler_export_download(id: ExportId) -> FileBody {
+///     FileBody::open(format!("/exports/{}.csv", id.0), "text/csv")
+/// }
+/// ```
+///
+/// Because the server knows the length and can seek, it can support `Range` requests without
+/// the handler doing anything.  It sends `Accept-Ranges: bytes` on every response, answers a
+/// single range with `206 Partial Content` and a `Content-Range` header, and answers a range
+/// outside the file with `416 Range Not Satisfiable`.  Multiple ranges in one request are served
+/// as the whole file, since hardly any client asks for them.  If the route has an ETag, `If-Range`
+/// is respected too, so a resumed download of a file that changed starts over.
+///
+/// The generated client gets a download helper, which writes to a file and picks up where it left
+/// off if that file is already partly there:
+///
+/// ```
+/// client.export_download_resume(ExportId(42), "export.csv")?;
+/// ```
+fn my_api_with_downloads() -> Api {
+    Api::new()
+        .path("export")
+        .alt(
+            alts![
+                path("download")
+                    .capture("id", "ExportId")
+                    .ret_file(GET),
+            ]
+        )
+}
+
+
+/// Money should never go over the wire as a JSON float.  Since `rust_decimal::Decimal` and
+/// `bigdecimal::BigDecimal` are so common, they should have built-in wire encodings, picked with
+/// `DecimalEncoding`:
+///
+/// -   `DecimalEncoding::String` sends `"12.30"`.  This is the default, since every language can
+///     decode it without losing precision.
+/// -   `DecimalEncoding::ScaledInteger(2)` sends `1230`, the value multiplied by 10^2.  Values
+///     with more decimal places than the scale are rejected rather than rounded.
+///
+/// The encoding can be set on the `Api` and overridden for a single field with
+/// `#[field(decimal = ..)]`.  The OpenAPI output describes the field as `type: string,
+/// format: decimal` or `type: integer, format: scaled-decimal-2`, and the foreign-language
+/// clients decode it into the language's own decimal type where there is one.
+///
+/// To make floating-point money harder to add by accident, `ApiSchema` warns about `f32` and
+/// `f64` fields whose name looks like money (`price`, `amount`, `total`, ...).
+#[derive(ApiSchema)]
+struct Invoice {
+    id: InvoiceId,
+    total: Decimal,
+    #[field(decimal = ScaledInteger(2))]
+    tax_cents: Decimal,
+}
+
+fn my_api_with_decimals() -> Api {
+    Api::new()
+        .decimal_encoding(DecimalEncoding::String)
+        .path("invoice")
+        .alt(
+            alts![
+                path("get")
+                    .capture("id", "InvoiceId")
+                    .ret(GET, "Invoice"),
+            ]
+        )
+}
+
+
+/// When a type used in the `Api` changes, we want to know whether existing clients will keep
+/// working.  `check_compatibility` compares two sets of schemas (for instance, the schemas from
+/// the last release and the current ones) and reports every change that breaks the chosen rule,
+/// like a schema registry does:
+///
+/// -   `Compatibility::Backward`: new servers can read requests from old clients.  Adding a
+///     required request field breaks this, while adding an optional one doesn't.
+/// -   `Compatibility::Forward`: old clients can read responses from new servers.  Removing a
+///     response field breaks this, and so does adding a variant to an enum without
+///     `#[non_exhaustive_wire]`.
+/// -   `Compatibility::Full`: both of the above.
+///
+/// Every finding says which type and field changed, which rule it breaks, and which routes use
+/// the type:
+///
+/// ```
+/// BREAKING (forward): User.name was removed
+///     used by responses of: user_create, users_get
+/// ```
+///
+/// The same checker runs as part of the API diff tool, so `saabanto diff` fails on breaking
+/// schema changes.
+fn check_user_schema_compatibility() {
+    let old = SchemaSet::from_file("schemas/v1.4.0.json");
+    let new = SchemaSet::from_api(&my_api());
+
+    check_compatibility(&old, &new, Compatibility::Full).report();
+}
+
+
+/// The generated client keeps a connection pool, but from the outside there is no way to see
+/// what it is doing.  `Client::pool_stats()` returns a snapshot of it:
+///
+/// ```
+/// struct PoolStats {
+///     hosts: Vec<HostPoolStats>,
+///     dns: DnsCacheStats,
+/// }
+///
+/// struct HostPoolStats {
+///     host: String,
+///     idle_connections: usize,
+///     active_connections: usize,
+///     queued_requests: usize,
+///     connections_opened_total: u64,
+/// }
+///
+/// struct DnsCacheStats {
+///     entries: usize,
+///     hits_total: u64,
+///     misses_total: u64,
+/// }
+/// ```
+///
+/// This is a plain snapshot rather than being tied to any metrics library, so applications can
+/// export it to whatever they already use, for instance by polling it every few seconds.
+/// Taking a snapshot is cheap and never blocks requests that are in flight.
+fn export_client_pool_stats(client: &Client) {
+    for host in client.pool_stats().hosts {
+        let labels = [("host", host.host.as_str())];
+        metrics::gauge("client_idle_connections", host.idle_connections as f64, &labels);
+        metrics::gauge("client_queued_requests", host.queued_requests as f64, &labels);
+    }
+}
+
+
+/// Routes can declare the headers they take with `.header(..)`, and the headers they send back
+/// with `.response_header(..)`.  On sensitive routes we want those declarations to be the whole
+/// story, so a `HeaderPolicy` says what happens to everything else.
+///
+/// For inbound headers:
+///
+/// -   `Inbound::Allow` passes undeclared headers through, as usual.
+/// -   `Inbound::Strip` removes them before anything (layers included) gets to see them.
+/// -   `Inbound::Reject` fails the request with `400`, naming the first undeclared header.
+///
+/// For outbound headers, `Outbound::Strip` drops any response header that wasn't declared,
+/// whether the handler or some middleware added it.  In debug builds it also logs a warning
+/// naming the header and the route, and reports it through `on_handler_error`.  Under
+/// `TestHarness` the report fails the test, so the undeclared header gets caught in tests
+/// without a panic on real traffic.
+///
+/// A small set of transport headers (`Content-Type`, `Content-Length`, `Host`, `Date`, etc) is
+/// always allowed, and layers that rely on other headers (`Authorization`, tracing headers) say
+/// so themselves, so turning on the policy doesn't break them.
+fn my_api_with_header_policy() -> Api {
+    Api::new()
+        .path("admin")
+        .alt(
+            alts![
+                path("users")
+                    .header("X-Admin-Reason", "String")
+                    .response_header("X-Audit-Id", "AuditId")
+                    .ret(GET, "Vec<User>")
+                    .header_policy(HeaderPolicy::new(Inbound::Strip, Outbound::Strip)),
+            ]
+        )
+}
+
+
+/// All of the generators (Rust client, TypeScript client, docs, OpenAPI) work from the same
+/// intermediate representation of the `Api`: every route, flattened, with all of its types
+/// resolved to schemas.  This IR should be public, together with a `CodegenTarget` trait, so that
+/// other crates can write their own generators:
+///
+/// ```
+/// trait CodegenTarget {
+///     /// A short name, used in error messages and for `--target` on the command line.
+///     fn name(&self) -> &str;
+///
+///     /// Generate files from the IR.  Paths are relative to the output directory.
+///     fn generate(&self, ir: &ApiIr, out: &mut OutputFiles) -> Result<(), CodegenError>;
+/// }
+/// ```
+///
+/// The built-in generators are implemented on top of this trait too, so a third-party target
+/// gets exactly the same information they do, and runs in the same pipeline.  Something like a C#
+/// client or an internal RPC format can live in its own crate, and be plugged in alongside the
+/// built-in targets:
+///
+/// ```
+/// $ saabanto generate --api my_api --target typescript --target csharp
+/// ```
+///
+/// The IR is versioned separately from the rest of the crate, so plugins don't break every time
+/// an unrelated part of the crate changes.
+fn generate_with_plugins() {
+    Codegen::new(&my_api())
+        .target(TypeScriptTarget::default())
+        .target(CSharpTarget::new("MyApi.Client"))
+        .out_dir("generated")
+        .run();
+}
+
+
+/// Whenever the generated server rejects a request because of a quota, a rate limit, or because
+/// it is overloaded, it should tell the client when to come back.  These responses (`429` and
+/// `503`) carry a `Retry-After` header, along with a small JSON body with more detail:
+///
+/// ```
+/// HTTP/1.1 429 Too Many Requests
+/// Retry-After: 30
+///
+/// { "error": "rate_limited", "retry_after_ms": 30000, "scope": "principal" }
+/// ```
+///
+/// The generated client turns this into a typed `RetryAdvice` on the error:
+///
+/// ```
+/// match client.user_create(id, name) {
+///     Err(ClientError::RateLimited(advice)) => sleep(advice.retry_after),
+///     ...
+/// }
+///
+/// struct RetryAdvice {
+///     retry_after: Duration,
+///     scope: RetryScope,  // just this route, this principal, or the whole server
+/// }
+/// ```
+///
+/// The client's retry middleware uses the advice directly, waiting at least `retry_after` before
+/// trying again, and giving up immediately if that is longer than the remaining `Deadline`.
+/// Servers that only send `Retry-After` (as a number of seconds or as a date) are understood too.
+fn my_api_client_retry_advice() -> Client {
+    Client::builder("https://api.example.com")
+        .middleware(Retry::new().max_attempts(3).respect_retry_advice(true))
+        .build()
+}
+
+
+/// Mirroring shows how a rewritten handler behaves, but at some point real answers have to come
+/// from it.  `.split(percent, handler)` sends a percentage of a route's traffic to an alternative
+/// handler with the same signature, and the rest to the normal one.
+///
+/// Assignment is sticky: it is decided by hashing the authenticated principal (or the session id,
+/// for unauthenticated routes) together with the route name, so a given user always sees the
+/// same handler instead of flipping back and forth between requests.  Requests with neither are
+/// assigned randomly.
+///
+/// Every request records which arm it was served by, as a `split_arm` label on the route's
+/// metrics and a field on its tracing span, so the two arms can be compared.  Raising the
+/// percentage step by step, and finally making the new handler the real one, is a canary
+/// rollout.
+///
+/// The split percentage can also be changed at runtime with `Server::set_split(..)`, so a bad
+/// canary can be rolled back without a deploy.
+fn my_api_with_split() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .split(10, handler_users_get_v2),
+            ]
+        )
+}
+
+
+/// Every route should have at least one test.  `generate_test_skeletons!` writes a starting point
+/// for each of them: a `#[tokio::test]` that starts the generated server on a random local port
+/// with the test harness, calls the route with the generated client using the route's example
+/// inputs, and checks that the call succeeded:
+///
+/// ```
+/// #[tokio::test]
+/// async fn test_user_create() {
+///     let harness = TestHarness::start(my_api_server()).await;
+///     let client = harness.client();
+///
+///     let (id, name) = fixtures::user_create_request();
+///     let result = client.user_create(id, name).await;
+///
+///     assert!(result.is_ok());
+///     // TODO: check the returned user.
+/// }
+/// ```
+///
+/// The skeletons are written to a file once, and after that they belong to us to fill in with
+/// real assertions.  Running the macro again doesn't touch existing tests, and only appends
+/// skeletons for routes that don't have a `test_<operation>` function yet.  With
+/// `deny_missing = true`, it fails the build instead, so no new route can be merged untested.
+generate_test_skeletons!(my_api_with_examples,
+    out_file = "tests/routes.rs"
+);
+
+
+/// Some backends will happily read an arbitrarily long URL into memory.  The generated server
+/// should check URLs itself, before routing, against limits set on the `Api`:
+///
+/// -   `max_url_length`: longer URLs get `414 URI Too Long`.
+/// -   `max_query_params`: more query parameters than this get `400 Bad Request`.
+/// -   `duplicate_query_keys`: what to do when the same key appears twice, like
+///     `?sort=true&sort=false`.  `DuplicateKeys::Reject` returns `400`, `DuplicateKeys::First`
+///     and `DuplicateKeys::Last` keep one of them.  Parameters declared as `Vec<T>` always
+///     accept repeated keys, since that is how they are sent.
+///
+/// The defaults are generous (8 KiB, 100 parameters, `Reject`), so most applications never need
+/// to touch them.  Routes taking many parameters can raise `max_query_params` for just that
+/// route.  The docs list the limits, and the rejections go through `on_request_rejected` like
+/// any other.
+fn my_api_with_url_limits() -> Api {
+    Api::new()
+        .max_url_length(4096)
+        .max_query_params(20)
+        .duplicate_query_keys(DuplicateKeys::Reject)
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Handlers that can fail return a `Result<T, ApiError>`, where `ApiError` is our own error enum.
+/// Rather than each route saying which status code each variant turns into, the mapping can be
+/// given once on the `Api` with `.error_map::<ApiError>(..)`.
+///
+/// The function turns an error into an `ErrorResponse`: a status code, an RFC 7807 problem type,
+/// and a title.  Every route whose handler returns `ApiError` uses it, and the generated server
+/// renders the response as `application/problem+json`.
+///
+/// Since `ApiError` derives `ApiSchema`, the generator can see every variant, and call the map on
+/// a sample of each to find out which responses are possible.  So every route returning
+/// `ApiError` documents a `404`, `409`, and `500` below, without repeating them route by route.
+///
+/// The generated client maps problem responses back to `ApiError` using the problem type, so
+/// `Err(ApiError::UserExists { .. })` on the server is `Err(ApiError::UserExists { .. })` on the
+/// client.
+#[derive(ApiSchema)]
+enum ApiError {
+    UserNotFound { id: UserId },
+    UserExists { id: UserId },
+    Internal,
+}
+
+fn api_error_response(e: &ApiError) -> ErrorResponse {
+    match e {
+        ApiError::UserNotFound { .. } => ErrorResponse::new(404, "user-not-found", "No user"),
+        ApiError::UserExists { .. } => ErrorResponse::new(409, "user-exists", "User exists"),
+        ApiError::Internal => ErrorResponse::new(500, "internal", "Internal error"),
+    }
+}
+
+fn my_api_with_error_map() -> Api {
+    Api::new()
+        .error_map::<ApiError>(api_error_response)
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "Result<User, ApiError>"),
+            ]
+        )
+}
+
+
+/// A broken schema or a backend wiring mistake usually only shows up when the first real
+/// request hits the route that is affected.  With `.preflight()`, the server checks every route
+/// on startup, before it starts accepting connections.
+///
+/// For every route with examples, the preflight decodes the example request the way the route's
+/// extractors would, and encodes the example response with the route's serializer, without
+/// calling the real handler.  This checks that the schemas, codec settings, and backend wiring
+/// all agree with each other, and warms up anything that is lazily initialized (buffer pools,
+/// regex caches, serializer state) before traffic arrives.
+///
+/// If any route fails, the server logs every failure and refuses to start, so a bad deploy fails
+/// its health checks instead of failing requests.  `.preflight_report_only()` logs failures but
+/// starts anyway.
+fn serve_my_api_with_preflight() {
+    Server::bind("[::]:8080")
+        .preflight()
+        .serve(my_api_server());
+}
+
+
+/// Batch tools built on the generated client tend to send requests as fast as they can, and then
+/// trip the server's rate limits.  The `RateLimit` client middleware keeps them under a limit on
+/// the client side instead.
+///
+/// Limits can be given per host, per operation, or both, as a rate (requests per second, with a
+/// burst size) and a maximum number of requests in flight.  When a call would go over a limit,
+/// the middleware either queues it until there is room, or fails it with
+/// `ClientError::LocalRateLimited`, depending on `OnLimit`.  Queued calls are served in the order
+/// they were made, and the queue has a maximum length, so a runaway loop still fails instead of
+/// queueing forever.
+///
+/// Queueing time counts against the call's `Deadline`, so a call that would have to wait longer
+/// than its deadline fails right away.
+///
+/// When the server answers with `RetryAdvice` anyway, the middleware pauses the affected host or
+/// operation for `retry_after`, instead of letting the rest of the queue run into the same limit.
+fn my_api_client_rate_limited() -> Client {
+    Client::builder("https://api.example.com")
+        .middleware(
+            RateLimit::new()
+                .per_host(RateLimitRule::per_second(50).burst(10).max_in_flight(8))
+                .operation("user_create", RateLimitRule::per_second(5))
+                .on_limit(OnLimit::Queue { max_queued: 1000 })
+        )
+        .build()
+}
+
+
+/// Some response fields are text shown directly to end users, like an error message or a product
+/// description, and should come back in the user's language.
+///
+/// Fields can be marked `#[field(localized)]`, with the type `Localized<T>`.  Handlers fill in one
+/// value per locale they have:
+///
+/// ```
+/// Localized::new()
+///     .with("en", "Welcome!")
+///     .with("ja", "ようこそ！")
+/// ```
+///
+/// The generated server negotiates a locale from `Accept-Language` against the locales listed on
+/// the `Api`, and serializes every localized field as a plain `T` in that locale, falling back to
+/// the `Api`'s default locale when a field doesn't have the negotiated one.  The chosen locale is
+/// sent back in `Content-Language`, and the response gets `Vary: Accept-Language` so caches keep
+/// the languages apart.
+///
+/// Handlers that need to know the locale (to format dates, say) can take a `Locale` argument.  The
+/// docs list the supported locales and mark which fields are localized.
+#[derive(ApiSchema)]
+struct Greeting {
+    #[field(localized)]
+    message: Localized<String>,
+}
+
+fn my_api_with_localization() -> Api {
+    Api::new()
+        .locales(&["en", "ja"], "en")
+        .path("greeting")
+        .alt(
+            alts![
+                path("get")
+                    .ret(GET, "Greeting"),
+            ]
+        )
+}
+
+
+/// For an `Api` with hundreds of routes, a single OpenAPI file gets big enough that some tools
+/// can't handle it.  `generate_openapi!` can split the output by tag instead.  For
+/// `my_api_with_tags`, where `admin_users` is tagged `internal` and the other two routes have no
+/// tag, this writes:
+///
+/// ```
+/// openapi/index.yaml
+/// openapi/internal.yaml
+/// openapi/untagged.yaml
+/// openapi/components.yaml
+/// ```
+///
+/// -   Each tag gets its own complete document, with just the paths for routes with that tag.
+///     Routes without a tag go in `untagged.yaml`, and routes with several tags appear in each.
+/// -   Schemas used by more than one document go in `components.yaml`, and are referenced from
+///     the others with `$ref: "components.yaml#/components/schemas/User"`.  Schemas used by only
+///     one document stay in that document.
+/// -   `index.yaml` is a root document that `$ref`s the paths from every other document, for
+///     tools that want the whole thing.
+///
+/// Adding a route changes the documents for its own tags, and `index.yaml`.  If the route uses a
+/// schema that so far only one other document used, that schema moves into `components.yaml`,
+/// so that other document changes too.
+generate_openapi!(my_api_with_tags,
+    split = ByTag,
+    out_dir = "openapi"
+);
+
+
+/// Compressing a stream of server-sent events normally means the compressor holds on to events
+/// until it has a full buffer, so a compressed stream can sit on events for seconds.
+///
+/// For streaming routes, the compression layer should flush after every event instead, so each
+/// event goes out as soon as it is written, while still sharing the compression dictionary with
+/// everything before it.  For streams where that is too many flushes, `.flush_policy(..)` can
+/// flush at most every so often instead.
+///
+/// Handlers that want finer control can take a `Flush` argument, and call it to push out
+/// everything written so far:
+///
+/// ```
+/// fn handler_events(flush: Flush) -> impl Stream<Item = Event> {
+///     ...
+/// }
+/// ```
+///
+/// Clients that don't ask for compression with `Accept-Encoding` get an uncompressed stream as
+/// usual, and `Flush` does nothing for them.
+fn my_api_with_stream_compression() -> Api {
+    Api::new()
+        .path("events")
+        .alt(
+            alts![
+                path("stream")
+                    .ret_sse(GET, "Event")
+                    .compression(Compression::gzip())
+                    .flush_policy(FlushPolicy::AtMostEvery(Duration::from_millis(100))),
+            ]
+        )
+}
+
+
+/// When a call is slow, it is useful to know whether the time went to the server or to the
+/// network in between.  A `TimingLayer` on the server adds two headers to every response:
+///
+/// ```
+/// X-Response-Time: 12.4ms
+/// X-Server-Timestamp: 2020-01-02T10:00:00.123Z
+/// ```
+///
+/// `X-Response-Time` is measured from when the request was fully read until the response
+/// headers are written, so it doesn't include slow clients sending or receiving bodies.
+/// `X-Server-Timestamp` is when the response was produced.
+///
+/// The generated client parses both into typed fields on every response.  `X-Response-Time`
+/// becomes `meta.server_duration`, a `Duration`, and `X-Server-Timestamp` becomes
+/// `meta.server_timestamp`, a `SystemTime`:
+///
+/// ```
+/// let resp = client.users_get_with_meta(true);
+/// if let Some(server_duration) = resp.meta.server_duration {
+///     let network_time = resp.meta.elapsed - server_duration;
+///     ...
+/// }
+/// ```
+///
+/// Both are `Option`s, since servers without the layer don't send the headers.  The `elapsed`
+/// measurement on the client side is always there.
+fn serve_my_api_with_timing() {
+    Server::bind("[::]:8080")
+        .layer(TimingLayer::new())
+        .serve(my_api_server());
+}
+
+
+/// In a big service shared by several teams, the first question when a route starts failing is
+/// who owns it.  Routes (or groups of routes) can say, with `.owner(..)`.  An owner set on a group
+/// applies to every route inside it, unless a route sets its own.
+///
+/// The owner shows up in a few places:
+///
+/// -   the OpenAPI output, as an `x-owner` extension on every operation,
+/// -   the route table from `Server::print_routes()`, as an extra column,
+/// -   the tracing span and metrics labels of every request, so alerts can be routed to the team.
+///
+/// `ownership_report` produces a CODEOWNERS-style report listing every owner and the routes they
+/// own, plus the routes that have no owner at all:
+///
+/// ```
+/// team-identity   POST /user/create/<id>, GET /user/get
+/// team-billing    GET /invoice/<id>
+/// (unowned)       GET /admin/users
+/// ```
+fn my_api_with_owners() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("user")
+                    .owner("team-identity")
+                    .alt(
+                        alts![
+                            path("create")
+                                .capture("id", "UserId")
+                                .body("name", "Name")
+                                .ret(POST, "User"),
+                            path("get")
+                                .query("sort", "bool")
+                                .ret(GET, "Vec<User>"),
+                        ]
+                    ),
+                path("invoice")
+                    .capture("id", "InvoiceId")
+                    .ret(GET, "Invoice")
+                    .owner("team-billing"),
+                path("admin")
+                    .path("users")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+fn print_ownership_report() {
+    println!("{}", ownership_report(&my_api_with_owners()));
+}
+
+
+/// During a database migration, we want reads to keep working while writes are turned off.
+/// Groups of routes can be put into a read-only maintenance mode at runtime with
+/// `Server::freeze(..)`, without a deploy:
+///
+/// ```
+/// server.freeze(Freeze::group("user")
+///     .message("Scheduled database maintenance")
+///     .retry_after(Duration::from_secs(600)));
+///
+/// // ... later
+/// server.unfreeze("user");
+/// ```
+///
+/// While a group is frozen, its `GET` routes keep serving as usual.  Mutating routes return
+/// `503 Service Unavailable` with a `Retry-After` header and a structured body, without calling
+/// the handler:
+///
+/// ```
+/// {
+///     "error": "maintenance",
+///     "message": "Scheduled database maintenance",
+///     "retry_after_ms": 600000
+/// }
+/// ```
+///
+/// Without `.retry_after(..)`, there's no default: the header and `retry_after_ms` are left out.
+///
+/// `.idempotent()` routes don't count as read routes here: a route has to be `GET` (or be marked
+/// `.read_only()`) to keep working.
+///
+/// The generated client turns this body into its own `ClientError::Maintenance` variant with the
+/// message and `RetryAdvice`, so callers can show a proper maintenance notice instead of a
+/// generic server error.
+fn serve_my_api_freezable() {
+    let server = Server::bind("[::]:8080").start(my_api_server());
+    server.freeze(
+        Freeze::group("user")
+            .message("Scheduled database maintenance")
+            .retry_after(Duration::from_secs(600))
+    );
+}
+
+
+/// Everything the generators emit should come out in the same order every time.  Otherwise,
+/// regenerating the TypeScript client or the OpenAPI output shows up in code review as a wall of
+/// reordered lines, and the real changes get lost in it.
+///
+/// The rules are simple and the same for every generator:
+///
+/// -   Routes are emitted in the order they are declared in the `Api`.  This is the order
+///     `Api::routes()` returns, and the order the docs read in.
+/// -   Schemas, and anything else keyed by name (OpenAPI `components`, TypeScript interfaces),
+///     are sorted by name.
+/// -   Fields are emitted in declaration order, and enum variants in declaration order.
+///
+/// Internally, generators use `BTreeMap` or `IndexMap` everywhere, never `HashMap`, so the output
+/// can't depend on hash seeds.  Generated files also don't include timestamps or absolute paths.
+///
+/// There is a test that runs every built-in generator twice on the same `Api`, in separate
+/// processes, and checks that the output is byte-for-byte identical.  Plugins written against
+/// `CodegenTarget` get the IR in the same order, so they are deterministic as long as they keep
+/// it.
+fn check_generators_are_deterministic() {
+    let first = GeneratedOutput::run_in_subprocess(&my_api_with_tags(), Generators::all());
+    let second = GeneratedOutput::run_in_subprocess(&my_api_with_tags(), Generators::all());
+    assert_eq!(first.files(), second.files());
+}
+
+
+/// Defaults for optional body fields are usually hidden in `#[serde(default = "...")]` functions,
+/// where nothing but serde can see them.  `#[field(default = ...)]` declares the default as part
+/// of the schema instead, as a JSON value:
+///
+/// -   The generated server fills in the default when the field is missing from the request body,
+///     before the handler sees it.  The handler's type can then be a plain `T` instead of
+///     `Option<T>`.
+/// -   The OpenAPI output gives the field a `default:`, and leaves it out of `required`.
+/// -   The generated clients make the field optional, and leave it out of the request when it
+///     isn't given, so the server's default always wins.
+/// -   The mock server uses the default when generating example bodies for the field.
+///
+/// The default value is checked against the field's type when the schema is derived, so a default
+/// that doesn't decode is a compile error rather than a runtime one.
+#[derive(ApiSchema)]
+struct UserSearchRequest {
+    query: String,
+    #[field(default = 20)]
+    limit: u32,
+    #[field(default = "relevance")]
+    order: SearchOrder,
+}
+
+
+/// Some handlers do heavy CPU work or blocking IO, like resizing an image or calling a C library.
+/// Run directly on the async executor, they hold up every other request on the same worker.
+///
+/// `.blocking()` on a route makes the generated server run its handler on a dedicated pool of
+/// threads instead.  The handler is a normal non-async function:
+///
+/// ```
+/// fn handler_avatar_resize(id: UserId, image: Bytes) -> Bytes {
+///     // CPU-heavy work is fine here.
+///     ...
+/// }
+/// ```
+///
+/// By default the pool is tokio's `spawn_blocking` pool.  Groups of routes can have their own
+/// named pool with its own size, picked with `.blocking_on(..)`, so one kind of slow work can't
+/// use up the threads another needs.
+///
+/// A pool can also be backed by rayon, for purely CPU-bound work.
+///
+/// When a pool is full, requests wait for a free thread, counting against their `Deadline`.  The
+/// number of busy threads and waiting requests per pool is exposed in metrics.
+fn my_api_with_blocking() -> Api {
+    Api::new()
+        .blocking_pool("images", BlockingPool::threads(4))
+        .alt(
+            alts![
+                path("avatar")
+                    .path("resize")
+                    .capture("id", "UserId")
+                    .body("image", "Bytes")
+                    .ret(POST, "Bytes")
+                    .blocking_on("images"),
+                path("user")
+                    .path("import")
+                    .body("csv", "Bytes")
+                    .ret(POST, "()")
+                    .blocking(),
+            ]
+        )
+}
+
+
+/// Some operations take too long to finish within a single request, like exporting every user to
+/// a CSV file.  `.long_running(..)` on a `POST` route declares the usual pattern for these.
+///
+/// The route itself returns `202 Accepted` right away, with a typed `OperationId` and a
+/// `Location` header.  Two more routes are generated under `/operations`:
+///
+/// -   `GET /operations/<id>` returns an `OperationStatus`: pending, running (with optional
+///     progress), succeeded, or failed with an error.
+/// -   `GET /operations/<id>/result` returns the result, with the type declared on the route, once
+///     the operation has succeeded.
+///
+/// The handler starts the work and returns right away.  It gets an `Operations` handle to create
+/// the operation and report progress, and where operation state is kept is pluggable with an
+/// `OperationStore`:
+///
+/// ```
+/// fn handler_users_export(ops: Operations, request: ExportRequest) -> OperationId {
+///     let op = ops.create();
+///     let id = op.id();
+///     op.spawn(async move {
+///         op.progress(0.5);
+///         ...
+///     });
+///     id
+/// }
+/// ```
+///
+/// The generated client gets both the plain method and a `submit_and_wait` variant, which polls
+/// the status route with exponential backoff (honoring `Retry-After` on the status responses)
+/// until the operation finishes, and then fetches the result:
+///
+/// ```
+/// let export: ExportResult = client.users_export_submit_and_wait(request).await?;
+/// ```
+fn my_api_with_long_running() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("export")
+                    .body("request", "ExportRequest")
+                    .long_running("ExportResult"),
+            ]
+        )
+}
+
+
+/// When a handler fails with an internal error, the error tracker (Sentry, Rollbar, ...) should
+/// get enough context to tell what request caused it.  Today every team builds this by hand, and
+/// the report is missing whatever they forgot.
+///
+/// The generated server can build an `ErrorReport` itself, whenever a handler returns an error
+/// that maps to a `5xx`, or panics:
+///
+/// ```
+/// struct ErrorReport {
+///     route_name: String,
+///     request_id: RequestId,
+///     principal: Option<Principal>,
+///     params: Vec<(String, String)>,        // sensitive values already redacted
+///     error_chain: Vec<String>,             // the error, then each `source()` in turn
+///     panic: Option<PanicInfo>,
+/// }
+/// ```
+///
+/// Parameters are included the same way request recording does it, so anything declared
+/// `.sensitive(..)` is redacted, and bodies are left out entirely.
+///
+/// Reports go to an `ErrorReporter`, and integrations with particular trackers are just
+/// implementations of it.  Reporting happens in the background after the response has been sent,
+/// so a slow tracker doesn't slow down the error response.
+fn serve_my_api_with_error_reports() {
+    Server::bind("[::]:8080")
+        .error_reporter(SentryReporter::new(sentry_dsn()))
+        .serve(my_api_server());
+}
+
+
+/// Calls between our own services need authentication too, but API keys are a poor fit there.
+/// `Auth::ServiceJwt` and `Auth::Spiffe` cover the two setups we use.
+///
+/// -   `Auth::ServiceJwt` expects a bearer JWT.  The server fetches the signing keys from the
+///     issuer's JWKS URL and caches them (refetching when it sees an unknown `kid`, but at most
+///     once a minute), then checks the signature, expiry, issuer, and audience.
+/// -   `Auth::Spiffe` takes the SPIFFE ID from the client certificate of an mTLS connection, and
+///     checks it against the trust domain.
+///
+/// Either way, handlers get a typed `ServicePrincipal` naming the calling service, and
+/// `.allow_services(..)` on a route rejects every other caller with `403`:
+///
+/// ```
+/// fn handler_users_sync(caller: ServicePrincipal, users: Vec<User>) -> () {
+///     ...
+/// }
+/// ```
+///
+/// On the client side, a `ServiceTokenSource` mints tokens (or fetches them from a token
+/// endpoint), caches them until shortly before they expire, and attaches them to every request,
+/// so calling code never deals with tokens at all.
+fn my_api_with_service_auth() -> Api {
+    Api::new()
+        .auth(Auth::ServiceJwt(
+            Jwt::jwks("https://auth.internal/.well-known/jwks.json")
+                .issuer("https://auth.internal")
+                .audience("my-api")
+        ))
+        .path("internal")
+        .alt(
+            alts![
+                path("users")
+                    .path("sync")
+                    .body("users", "Vec<User>")
+                    .ret(POST, "()")
+                    .allow_services(&["billing", "search-indexer"]),
+            ]
+        )
+}
+
+fn my_api_client_service_auth() -> Client {
+    Client::builder("https://my-api.internal")
+        .service_tokens(ServiceTokenSource::client_credentials("https://auth.internal/token"))
+        .build()
+}
+
+
+/// The compatibility checker compares two versions of the schemas, but what consumers care about
+/// is narrower: "will the SDK version I have installed still work?"
+///
+/// Each built client SDK can carry the IR it was generated from.  `sdk_compatibility` takes the
+/// IRs of the SDK releases we still support and checks the current `Api` against each of them:
+/// for every route the old SDK knows about, can that SDK still decode what the current server
+/// sends?  This checks that fields the SDK requires are still always present, that types haven't
+/// changed underneath it, and that the server can't send enum variants the SDK doesn't know about
+/// (unless the enum is `#[non_exhaustive_wire]`).
+///
+/// The result is a matrix of SDK versions against routes, which can be printed or published as
+/// JSON next to the docs:
+///
+/// ```
+///             user_create  users_get  users_export
+/// sdk 1.2.0   ok           ok         (not in sdk)
+/// sdk 1.3.0   ok           BROKEN     ok
+/// sdk 1.4.0   ok           ok         ok
+///
+/// sdk 1.3.0 / users_get: field `User.name` changed from `string` to `object`
+/// ```
+fn print_sdk_compatibility_matrix() {
+    let releases = vec![
+        ApiIr::from_file("sdk-irs/1.2.0.json"),
+        ApiIr::from_file("sdk-irs/1.3.0.json"),
+        ApiIr::from_file("sdk-irs/1.4.0.json"),
+    ];
+
+    sdk_compatibility(&my_api_with_export(), &releases).print();
+}
+
+fn my_api_with_export() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .name("user_create"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .name("users_get"),
+                path("export")
+                    .body("request", "ExportRequest")
+                    .long_running("ExportResult")
+                    .name("users_export"),
+            ]
+        )
+}
+
+
+/// When a route's path changes, old clients and bookmarked links still use the old path.
+/// `.moved_to(..)` declares that an old path now lives at another route.
+///
+/// The old path doesn't get a handler.  Instead, the generated server answers it with a
+/// permanent redirect to the new route: `308 Permanent Redirect` for routes that take a body (so
+/// the method and body are kept), and `301 Moved Permanently` for `GET`.  Captures and query
+/// parameters are carried over to the new URL, and renamed according to `.remap(..)` where the
+/// names differ.  Since both routes are declared in the `Api`, it is checked that every parameter
+/// of the new route gets a value.
+///
+/// The docs list the old path under the new route, as a redirect.  The generated client always
+/// calls the new path directly, but if it is pointed at a server that redirects it anyway (an old
+/// client against a new server, say), it follows the redirect and logs a deprecation warning
+/// naming the new route.
+fn my_api_with_moved_routes() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("users")
+                    .capture("user_id", "UserId")
+                    .ret(GET, "User")
+                    .name("user_get"),
+                path("user")
+                    .path("show")
+                    .capture("id", "UserId")
+                    .moved_to("user_get")
+                    .remap("id", "user_id"),
+            ]
+        )
+}
+
+
+/// Product analytics want to know when things happen in business terms ("a user was created"),
+/// not HTTP terms.  Right now that means grepping logs.
+///
+/// Business events are plain structs deriving `BusinessEvent`.  Handlers emit them with `track!`:
+///
+/// ```
+/// fn handler_user_create(userId: UserId, name: Name) -> User {
+///     ...
+///     track!(UserCreated { id: userId });
+///     ...
+/// }
+/// ```
+///
+/// `track!` attaches the current `RequestContext` to the event (route name, request id,
+/// principal, SDK version from the telemetry headers) and hands it to an `EventSink`.  Sinks
+/// are pluggable, so events can go to a queue, a data warehouse, or just the log.  Events are
+/// buffered and sent in the background, so a slow sink never slows down requests.
+///
+/// By default, events from a request whose handler ends up failing are dropped, since the thing
+/// they describe usually didn't actually happen.  `track_always!` sends the event regardless.
+///
+/// Routes can list the events they may emit with `.emits::<..>()`, which puts them in the docs
+/// so the analytics team knows what exists.
+#[derive(BusinessEvent)]
+struct UserCreated {
+    id: UserId,
+}
+
+fn serve_my_api_with_events() {
+    Server::bind("[::]:8080")
+        .event_sink(KafkaEventSink::new("business-events"))
+        .serve(my_api_server());
+}
+
+
+/// Two people editing the same user at the same time will overwrite each other's changes,
+/// unless updates are conditional on the version that was read.  `.etag_guarded()` declares this
+/// for a resource.
+///
+/// The `GET` route's handler returns the resource along with its version, which the generated
+/// server sends as an `ETag`.  The update route (`PUT` or `PATCH`) then *requires* an `If-Match`
+/// header:
+///
+/// -   a missing `If-Match` gets `428 Precondition Required`,
+/// -   an `If-Match` that doesn't match the current version gets `412 Precondition Failed`,
+///     without the update being applied.
+///
+/// The handler is given the expected `Version` to compare against in the same transaction as the
+/// update, since only it can do that atomically.
+///
+/// The generated client makes this hard to get wrong.  Reading the resource returns a
+/// `Versioned<User>`, and the update method takes a `VersionedUpdate` built from it, so there is
+/// no way to update without the version you read:
+///
+/// ```
+/// let user = client.user_get(UserId(1))?;
+/// let update = user.update(|u| u.name = Name("Bob".to_string()));
+/// match client.user_update(update) {
+///     Err(ClientError::VersionConflict { .. }) => { /* re-read and try again */ }
+///     ...
+/// }
+/// ```
+fn my_api_with_etag_guard() -> Api {
+    Api::new()
+        .path("user")
+        .capture("id", "UserId")
+        .etag_guarded()
+        .alt(
+            alts![
+                path("show")
+                    .ret(GET, "User"),
+                path("update")
+                    .body("user", "User")
+                    .ret(PUT, "User"),
+            ]
+        )
+}
+
+
+/// The name of a parameter or field in Rust and its name on the wire don't have to be the same.
+/// `.wire_name(..)` on a parameter, or `#[field(wire_name = "...")]` on a schema field, sets the
+/// wire name explicitly.  After that, the Rust name can be changed freely without touching the
+/// wire format, and the other way around.
+///
+/// This is a separate layer from serde's `rename`, because the generators need to see it: the
+/// wire name is what appears in the docs, OpenAPI, and foreign-language clients, while the Rust
+/// name is what appears in handlers and the Rust client.
+///
+/// The API diff tool compares wire names, not Rust names.  Renaming a Rust field that has a
+/// `wire_name` is not a change at all as far as the diff tool is concerned.  Renaming a field
+/// *without* one changes its wire name too, and is flagged as a breaking change, with a hint
+/// suggesting `wire_name` to keep the old one.
+#[derive(ApiSchema)]
+struct UserSettings {
+    #[field(wire_name = "userName")]
+    display_name: String,
+}
+
+fn my_api_with_wire_names() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .wire_name("sortResults")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// JSON numbers and Rust numbers don't line up perfectly.  JavaScript clients lose precision on
+/// integers above 2^53, and JSON has no way at all to write `NaN` or `Infinity`.  The `Api`
+/// should have explicit, consistent policies for both.
+///
+/// `BigNumbers` decides how integer types that can hold values an f64 can't represent exactly
+/// (`i64`, `u64`, `i128`, `u128`) go over the wire.  It applies per type, not per value: with
+/// `AsString`, every `i64` is sent as a string, even `1`, so a field's wire type never depends on
+/// how big its value happens to be.  Smaller types like `i32` and `u32` are always plain numbers.
+///
+/// -   `BigNumbers::AsString` sends them as JSON strings.  The OpenAPI output uses
+///     `type: string, format: int64`, and the foreign-language clients decode them into a
+///     bigint.
+/// -   `BigNumbers::Exact` sends them as JSON numbers, relying on `serde_json`'s
+///     `arbitrary_precision` on both sides to keep every digit.
+///
+/// `NonFinite` decides what happens to `NaN` and infinite floats in responses:
+///
+/// -   `NonFinite::Reject` fails serialization, so the handler's bug shows up as a `500` with a
+///     clear error, instead of as bad data.
+/// -   `NonFinite::Null` sends them as `null`, and documents the field as nullable.
+///
+/// Both policies are set on the `Api`, and apply equally to the server, the clients, and the
+/// schemas.
+fn my_api_with_number_policies() -> Api {
+    Api::new()
+        .big_numbers(BigNumbers::AsString)
+        .non_finite(NonFinite::Reject)
+        .path("stats")
+        .alt(
+            alts![
+                path("get")
+                    .ret(GET, "Stats"),
+            ]
+        )
+}
+
+
+/// Every service ends up writing the same code to read its bind address, TLS files, and limits
+/// from a config file and environment variables.  `ServerConfig` covers the settings the serve
+/// helpers already take, and can be deserialized:
+///
+/// ```
+/// [server]
+/// bind = "[::]:8443"
+///
+/// [server.tls]
+/// cert = "/etc/my-api/cert.pem"
+/// key = "/etc/my-api/key.pem"
+///
+/// [limits]
+/// max_url_length = 4096
+///
+/// [cors]
+/// allow_origins = ["https://app.example.com"]
+///
+/// [features]
+/// experimental_routes = false
+/// ```
+///
+/// `ServerConfig::load()` layers several sources, where later ones override earlier ones:
+/// built-in defaults, then the config file, then environment variables (`MY_API__SERVER__BIND`
+/// for `server.bind`, with the prefix set from the `Api`'s name).  Errors say which source and
+/// key had the bad value.
+///
+/// `Server::from_config` applies the whole thing.  Settings that are also declared on the `Api`
+/// (like limits and CORS) can only be tightened by the config file, not loosened, unless the
+/// `Api` says otherwise.
+fn serve_my_api_from_config() {
+    let config = ServerConfig::load()
+        .file("/etc/my-api/config.toml")
+        .env_prefix("MY_API")
+        .finish();
+
+    Server::from_config(&config)
+        .serve(my_api_server());
+}
+
+
+/// Our infrastructure APIs are used by automation that wants to know what a change *would* do
+/// before doing it.  `.dry_run()` on a mutating route adds standard support for this.
+///
+/// A request can ask for a dry run with `?dry_run=true` or a `Dry-Run: true` header.  Either way,
+/// the handler gets a typed `DryRun` argument, and is responsible for validating without
+/// committing anything.  Validation failures are returned as an `ApiError`, and mapped to a
+/// response by the `Api`'s `.error_map(..)` like any other error:
+///
+/// ```
+/// fn handler_user_create(dry_run: DryRun, userId: UserId, name: Name) -> Result<User, ApiError> {
+///     let user = validate(userId, name)?;
+///     if dry_run.is_dry_run() {
+///         return Ok(user);
+///     }
+///     ...
+/// }
+/// ```
+///
+/// Responses to dry runs carry a `Dry-Run: true` header, so there is never any doubt about
+/// whether something actually happened.  The generated client gets a `dry_run()` variant of every
+/// such method, which returns a `Simulated<T>` instead of a `T`, so simulated results can't be
+/// mistaken for real ones.
+///
+/// Routes that don't declare `.dry_run()` reject dry-run requests with `400`, rather than
+/// silently performing the real change.
+fn my_api_with_dry_run() -> Api {
+    Api::new()
+        .error_map::<ApiError>(api_error_response)
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "Result<User, ApiError>")
+                    .dry_run(),
+            ]
+        )
+}
+
+
+/// Since `generate_server!` and `generate_client!` expand the whole `Api` at once, changing a
+/// single route re-expands everything, and large `Api`s get slow to build.
+///
+/// The macros can cache their output instead.  Each route's generated code depends only on the
+/// route's own part of the IR, plus the schemas it uses, so the macros hash these and keep the
+/// expanded code in `target/saabanto-cache/`, keyed by the hash.  On the next build, only routes
+/// whose hash changed are expanded again, and the rest are read back from the cache.
+///
+/// The cache key also includes the version of the crate and the macro options, so upgrading or
+/// changing options never picks up stale output.  Setting `SAABANTO_NO_CACHE=1` turns the cache
+/// off, for when it is suspected of causing trouble.
+///
+/// Caching is turned on with an option on the existing `generate_server!` call:
+///
+/// ```
+/// generate_server!(my_api,
+///     cache = true,
+///     server_alts![
+///         handler_user_create,
+///         handler_users_get,
+///     ]
+/// );
+/// ```
+///
+/// The cache only helps with macro expansion.  Making rustc itself compile less on each change
+/// needs the generated code split into separate modules as well.
+struct ExpansionCacheKey {
+    route_ir_hash: u64,
+    crate_version: &'static str,
+    options_hash: u64,
+}
+
+
+/// `.paginated(..)` declares that a list route takes `page` and `per_page` query parameters and
+/// returns one page of results along with a link to the next one.  The `Pagination` also
+/// declares how big a page can be:
+///
+/// -   `default_page_size` is used when the request has no `per_page`.
+/// -   `max_page_size` is the largest `per_page` the server will honor.  Larger values are clamped
+///     down to it, rather than rejected, and the response's `per_page` field says what was
+///     actually used.
+///
+/// The clamping happens in the generated server, before the handler is called, so a handler
+/// never sees a `per_page` above the maximum and can't forget to check.
+///
+/// The docs and OpenAPI output give `per_page` its `default:` and `maximum:`.  The generated
+/// client's auto-paginator, which walks every page for you, asks for `max_page_size` per page by
+/// default, so it makes as few requests as possible without ever being clamped.
+///
+/// ```
+/// let mut users = client.users_list_all(true);
+/// while let Some(user) = users.next().await {
+///     ...
+/// }
+/// ```
+fn my_api_with_pagination() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("list")
+                    .query("sort", "bool")
+                    .ret(GET, "Page<User>")
+                    .paginated(
+                        Pagination::page_number()
+                            .default_page_size(50)
+                            .max_page_size(200)
+                    ),
+            ]
+        )
+}
+
+
+/// A client running on a small device can't afford to read an unexpectedly huge response into
+/// memory.  The generated client can be given a maximum response body size, with a separate limit
+/// for particular operations.
+///
+/// The limit is checked against `Content-Length` before reading anything, and again while
+/// reading, for responses without one.  As soon as the body goes over the limit, the client stops
+/// reading, closes the connection, and returns `ClientError::ResponseTooLarge` with the
+/// operation name and the limit that was hit.  The connection is not returned to the pool, since
+/// the rest of the body is still on it.
+///
+/// For streaming methods (like the `_stream` variants of big list routes), the limit applies to
+/// each element instead of the whole response.
+fn my_api_client_body_limits() -> Client {
+    Client::builder("https://api.example.com")
+        .max_response_size(1024 * 1024)
+        .operation_max_response_size("users_get", 8 * 1024 * 1024)
+        .build()
+}
+
+
+/// Some responses are part JSON and part binary, like a generated report with its metadata.
+/// Base64-encoding the binary part into the JSON makes it a third bigger, and forces both sides
+/// to hold the whole thing in memory.
+///
+/// `.ret_multipart(..)` declares a route that returns `multipart/mixed` instead, with a list of
+/// named, typed parts:
+///
+/// ```
+/// fn handler_report_generate(id: ReportId) -> Multipart<ReportParts> {
+///     Multipart::new(ReportParts {
+///         metadata: ReportMetadata { ... },
+///         file: FileBody::open("/reports/42.pdf", "application/pdf"),
+///     })
+/// }
+/// ```
+///
+/// JSON parts are serialized like any other response.  Binary parts are streamed straight from
+/// their reader, without being buffered.  Each part gets a `Content-Disposition` with its name,
+/// so the client can find parts by name instead of by position.
+///
+/// The generated client returns the parts in the same order they are declared.  JSON parts come
+/// back already decoded, and binary parts are streams, which have to be read in order as the
+/// response arrives:
+///
+/// ```
+/// let report = client.report_generate(ReportId(42)).await?;
+/// let metadata: ReportMetadata = report.metadata;
+/// report.file.copy_to(&mut File::create("report.pdf")?).await?;
+/// ```
+fn my_api_with_multipart() -> Api {
+    Api::new()
+        .path("report")
+        .alt(
+            alts![
+                path("generate")
+                    .capture("id", "ReportId")
+                    .ret_multipart(GET, &[
+                        Part::json("metadata", "ReportMetadata"),
+                        Part::binary("file", "application/pdf"),
+                    ]),
+            ]
+        )
+}
+
+
+/// Right after a deploy, ops wants to know if the important routes still work, without running
+/// the whole test suite.  `.smoke_test(..)` on a route gives it a call to make with specific
+/// inputs, and the expected kind of outcome.
+///
+/// `generate_smoke_tests!` produces a small binary that makes all of these calls against a base
+/// URL given on the command line, using the generated client, and prints a result per route:
+///
+/// ```
+/// $ my-api-smoke --base-url https://api.staging.example.com --api-key-env SMOKE_KEY
+/// users_get     ok      43ms
+/// user_get      ok      38ms
+/// user_create   skipped (mutating route, pass --allow-mutating to run)
+/// 2 passed, 0 failed, 1 skipped
+/// ```
+///
+/// It exits non-zero when anything fails, so it can gate the rest of a deploy pipeline.  Smoke
+/// tests on mutating routes are only run with `--allow-mutating`, so running the binary against
+/// production by mistake doesn't create anything.
+fn my_api_with_smoke_tests() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .smoke_test(SmokeTest::new().query("sort", json!(true)).expect_ok()),
+                path("show")
+                    .capture("id", "UserId")
+                    .ret(GET, "User")
+                    .name("user_get")
+                    .smoke_test(SmokeTest::new().capture("id", json!(1)).expect_ok()),
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .name("user_create")
+                    .smoke_test(
+                        SmokeTest::new()
+                            .capture("id", json!(9999))
+                            .body("name", json!("smoke-test"))
+                            .expect_ok()
+                    ),
+            ]
+        )
+}
+
+generate_smoke_tests!(my_api_with_smoke_tests,
+    bin_name = "my-api-smoke"
+);
+
+
+/// The mock server from `generate_mock_server!` answers every route with its examples (or values
+/// generated from the schema), so frontend teams can work without the real backend.  It is a
+/// little too perfect, though: every response is instant and successful.
+///
+/// The mock server can instead read a sandbox config, giving each route a latency distribution
+/// and an error rate:
+///
+/// ```
+/// [default]
+/// latency = { distribution = "lognormal", median_ms = 80, p99_ms = 900 }
+///
+/// [routes.user_create]
+/// latency = { distribution = "fixed", ms = 2000 }
+/// error_rate = 0.1
+/// errors = [{ status = 409, weight = 3 }, { status = 500, weight = 1 }]
+/// ```
+///
+/// Errors are picked from the responses the route actually declares (those from `.error_map(..)`
+/// included), so the frontend only ever sees errors the real server could send.  The config is
+/// reloaded when the file changes, so conditions can be adjusted while the frontend is running.
+/// Setting a seed makes the sequence of delays and errors reproducible.
+generate_mock_server!(my_api_with_error_map,
+    sandbox_config = "sandbox.toml"
+);
+
+
+/// Types used in the `Api` usually already have doc comments.  `#[derive(ApiSchema)]` should
+/// pick those up, so the same descriptions don't have to be written again for the docs.
+///
+/// The doc comment on a struct or enum becomes the schema's description, and the doc comment on
+/// each field or variant becomes that field's or variant's description.  From there they flow
+/// into the generated docs, the OpenAPI `description`s, the doc comments on the generated Rust
+/// client's types, and the comments in the foreign-language clients (JSDoc, Python docstrings,
+/// Go comments).
+///
+/// Markdown in doc comments is kept as-is, since OpenAPI descriptions are Markdown too.
+/// Intra-doc links like ``[`UserId`]`` are turned into links to the `UserId` schema where the
+/// output format supports it, and into plain text where it doesn't.
+///
+/// A `#[field(description = "...")]` attribute overrides the doc comment, for the rare cases
+/// where the internal explanation shouldn't be published.
+fn my_api_with_documented_user() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("show")
+                    .capture("id", "UserId")
+                    .ret(GET, "DocumentedUser"),
+            ]
+        )
+}
+
+/// Someone with an account on our service.
+#[derive(ApiSchema)]
+struct DocumentedUser {
+    /// The user's unique id.  This never changes.
+    id: UserId,
+    /// The name shown to other users.  Doesn't have to be unique.
+    name: Name,
+    /// Internal scoring used for search ranking.
+    #[field(description = "Used for ordering search results.")]
+    rank: u32,
+}
+
+generate_openapi!(my_api_with_documented_user);
+
+
+/// When something goes wrong inside the generated wiring, the panic or log message doesn't say
+/// much.  The backtrace is full of anonymous closures from the macro expansion, and it is hard
+/// to tell which route was even involved.
+///
+/// In debug builds (`cfg(debug_assertions)`), the generated code wraps every extraction and
+/// serialization step with the route's context, so the message says exactly what failed:
+///
+/// ```
+/// saabanto: route `user_create` (POST /user/create/<id>): failed to extract capture `id`
+///     declared type: UserId
+///     raw value: "abc"
+///     cause: invalid digit found in string
+/// ```
+///
+/// The generated closures are also replaced with named functions
+/// (`__saabanto_user_create_extract`, `__saabanto_user_create_serialize`, ...), so backtraces
+/// point at the route by name.
+///
+/// Release builds keep the short messages and skip the extra bookkeeping, since this costs a bit
+/// of code size and time per request.  An option on the existing `generate_server!` call turns it
+/// on in release builds too:
+///
+/// ```
+/// generate_server!(my_api,
+///     debug_context = true,
+///     server_alts![
+///         handler_user_create,
+///         handler_users_get,
+///     ]
+/// );
+/// ```
+struct DebugContext {
+    operation: &'static str,
+    path_template: &'static str,
+    parameter: Option<&'static str>,
+    declared_type: &'static str,
+}
+
+
+/// Routes with lots of optional query parameters turn into client methods with long lists of
+/// `Option`s, where it is easy to pass `None` in the wrong place:
+///
+/// ```
+/// client.users_search("alice".to_string(), Some(true), None, Some(2), None);
+/// ```
+///
+/// For these routes, the generated client can also have a builder-style method.  Required
+/// parameters are taken up front, each optional parameter becomes a setter, and `send()` makes
+/// the call:
+///
+/// ```
+/// let users = client.users_search_builder("alice")
+///     .sort(true)
+///     .page(2)
+///     .send()
+///     .await?;
+/// ```
+///
+/// The builder is generated for any route with more than `builder_threshold` optional parameters
+/// (three by default), and for any route marked with `.client_builder()`.  The positional method
+/// is always still generated, so switching on the builder never breaks existing callers.  With
+/// four optional parameters, `users_search` below gets a builder without being marked.
+fn my_api_with_client_builders() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("search")
+                    .query("q", "String")
+                    .query("sort", "Option<bool>")
+                    .query("status", "Option<UserStatus>")
+                    .query("page", "Option<u32>")
+                    .query("per_page", "Option<u32>")
+                    .ret(GET, "Vec<User>")
+                    .name("users_search"),
+            ]
+        )
+}
+
+
+/// Every team has a bulk endpoint, and every one of them reports partial failures differently.
+/// `.bulk(..)` declares the standard shape for them.
+///
+/// The request body is a list of items, and the handler returns one `ItemResult` per item, in
+/// the same order:
+///
+/// ```
+/// fn handler_users_bulk_update(users: Vec<User>) -> Vec<ItemResult<User, ApiError>> {
+///     ...
+/// }
+/// ```
+///
+/// The generated server picks the status code from the results.  If every item succeeded, it is
+/// `200`, and if every item failed with the same error, it is that error's status.  Anything in
+/// between is `207 Multi-Status`, with a body giving the status and result or error of each item:
+///
+/// ```
+/// { "results": [
+///     { "index": 0, "status": 200, "value": { "id": 1, "name": "Alice" } },
+///     { "index": 1, "status": 404, "error": { "type": "user-not-found", ... } }
+/// ] }
+/// ```
+///
+/// The generated client returns a `BulkResult<User, ApiError>` regardless of the status, with
+/// helpers to split it up:
+///
+/// ```
+/// let result = client.users_bulk_update(users).await?;
+/// let (succeeded, failed) = result.partition();
+/// ```
+///
+/// A maximum number of items per request can be given, and it appears in the docs.
+fn my_api_with_bulk() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("bulk_update")
+                    .bulk(PUT, "User", "User", "ApiError")
+                    .max_items(500),
+            ]
+        )
+}
+
+
+/// The generated client follows redirects the way its HTTP library does by default, which isn't
+/// always what we want.  `RedirectPolicy` makes it explicit:
+///
+/// -   `RedirectPolicy::Follow { limit }` follows up to `limit` redirects.  This is the default,
+///     with a limit of 10.
+/// -   `RedirectPolicy::SameOrigin { limit }` only follows redirects to the same scheme, host,
+///     and port, and returns `ClientError::CrossOriginRedirect` otherwise.  Credentials are never
+///     sent to another origin either way.
+/// -   `RedirectPolicy::None` never follows redirects.
+///
+/// The policy is set on the client, and can be overridden for single operations.
+///
+/// For routes where the redirects themselves are the point, like resolving a short link, the
+/// `_with_meta` variant of the method exposes where the request ended up:
+///
+/// ```
+/// let resp = client.short_link_resolve_with_meta("abc123")?;
+/// println!("ended up at {}", resp.meta.final_url);
+/// for hop in &resp.meta.redirects {
+///     println!("  {} -> {}", hop.status, hop.location);
+/// }
+/// ```
+fn my_api_client_redirects() -> Client {
+    Client::builder("https://api.example.com")
+        .redirect_policy(RedirectPolicy::SameOrigin { limit: 5 })
+        .operation_redirect_policy("short_link_resolve", RedirectPolicy::Follow { limit: 20 })
+        .build()
+}
+
+
+/// When clients talk to a fleet where not every server runs the same version, they need to know
+/// what the particular server they are connected to supports.
+///
+/// With `.reflection()`, the generated server adds a `GET /.well-known/saabanto` route describing
+/// itself: the `Api` title and version, the names of the routes it serves (along with their
+/// stability levels), and any features that are turned on, like method overriding or
+/// experimental routes.  The route only lists names and versions, not full schemas, so it is safe
+/// to leave on in production.
+///
+/// The generated client gets a `capabilities()` method that fetches this and returns a typed
+/// report:
+///
+/// ```
+/// let caps = client.capabilities().await?;
+/// if caps.supports("users_search") {
+///     client.users_search_builder("alice").send().await?
+/// } else {
+///     client.users_get(true).await?  // older servers: filter locally
+/// }
+/// ```
+///
+/// For servers without the reflection route, `capabilities()` falls back to sending `OPTIONS`
+/// requests for the routes it knows about, and uses the `Allow` headers to see which exist.  The
+/// report is cached on the client for a configurable time, so checking it is cheap.
+fn my_api_with_reflection() -> Api {
+    Api::new()
+        .version("1.4.0")
+        .reflection()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Privacy reviews need to know which fields hold personal data, and how long it is kept.  Today
+/// that lives in a spreadsheet that is always out of date.  Schema fields can declare it instead:
+///
+/// -   `pii = "..."` gives the kind of personal data (`"email"`, `"name"`, `"ip_address"`, ...).
+/// -   `retention = "..."` gives how long the data may be kept, like `"90d"` or `"7y"`.
+///
+/// These go into the IR (so `CodegenTarget` plugins can see them), and into the OpenAPI output as
+/// `x-pii` and `x-retention` extensions.
+///
+/// The server layers that write data out use them too.  The request recorder, error reports, and
+/// logging/audit layers all treat `pii` fields as `.sensitive(..)` automatically, and redact them,
+/// so a new email field can't end up in the logs just because someone forgot to mark it.
+///
+/// `pii_report` lists every PII field in the `Api`, along with its retention and which routes
+/// accept or return it, as the starting point for a privacy review.
+#[derive(ApiSchema)]
+struct Contact {
+    id: UserId,
+    #[field(pii = "email", retention = "90d")]
+    email: String,
+    #[field(pii = "phone_number", retention = "90d")]
+    phone: Option<String>,
+}
+
+fn my_api_with_contacts() -> Api {
+    Api::new()
+        .path("contact")
+        .alt(
+            alts![
+                path("show")
+                    .capture("id", "UserId")
+                    .ret(GET, "Contact"),
+                path("update")
+                    .body("contact", "Contact")
+                    .ret(PUT, "Contact"),
+            ]
+        )
+}
+
+fn print_pii_report() {
+    println!("{}", pii_report(&my_api_with_contacts()));
+}
+
+
+/// Even with macro output cached, `generate_server!` and `generate_client!` still emit one big
+/// block of code, and rustc compiles it as a single unit.  For an `Api` with hundreds of routes,
+/// that one unit is where most of the build time goes.
+///
+/// With `split = ByRoute` (or `split = ByTag`), the macros emit a module per route (or per tag)
+/// instead, plus a small root module that wires them together:
+///
+/// ```
+/// mod my_api_server {
+///     mod user_create { ... }
+///     mod users_get { ... }
+///     ...
+///     pub fn my_api_server() -> ... { ... }
+/// }
+/// ```
+///
+/// Each module only refers to its own route's code and the shared schema types, so rustc's
+/// codegen units can be spread across cores, and a change to one route only recompiles that
+/// route's module and the root.  Combined with caching, this is what keeps incremental builds of
+/// large `Api`s fast.
+///
+/// Splitting is an option on the existing `generate_server!` call:
+///
+/// ```
+/// generate_server!(my_api,
+///     split = ByRoute,
+///     server_alts![
+///         handler_user_create,
+///         handler_users_get,
+///     ]
+/// );
+/// ```
+///
+/// There is a 300-route fixture `Api` in the benchmarks to compare clean and incremental build
+/// times with and without splitting, so we can check this actually pays off before making it the
+/// default.
+enum SplitMode {
+    None,
+    ByRoute,
+    ByTag,
+}
+
+
+/// Search endpoints tend to grow their own ad-hoc query syntax.  `.search::<UserSearch>()` gives
+/// them a small shared grammar instead, in a single `q` query parameter:
+///
+/// ```
+/// name:alice status:active created:[2020-01-01 TO 2020-06-30] AND NOT name:alice-bot
+/// ```
+///
+/// The fields that can be searched, and their types, come from the `UserSearch` struct.  The
+/// generated server parses `q` into a typed AST and hands it to the handler, so handlers never
+/// parse strings themselves, and can't be given a field or a value of the wrong type:
+///
+/// ```
+/// fn handler_users_search(query: SearchQuery<UserSearch>) -> Vec<User> {
+///     match query.expr() {
+///         SearchExpr::And(a, b) => ...,
+///         SearchExpr::Field(UserSearchField::Name(name)) => ...,
+///         ...
+///     }
+/// }
+/// ```
+///
+/// Queries that don't parse get a `400` with the position of the problem.  The docs for the
+/// route describe the grammar and list the searchable fields.
+///
+/// The generated client has a matching query builder, which produces a correctly escaped `q`:
+///
+/// ```
+/// let q = UserSearch::name("alice").and(UserSearch::status(UserStatus::Active));
+/// client.users_search(q).await?;
+/// ```
+#[derive(ApiSearch)]
+struct UserSearch {
+    name: String,
+    status: UserStatus,
+    created: Date,
+}
+
+fn my_api_with_search() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("search")
+                    .search::<UserSearch>()
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// Some resources have a natural modification time, which is simpler than computing an ETag.
+/// `.last_modified()` on a `GET` route means the handler returns the resource together with its
+/// modification time:
+///
+/// ```
+/// fn handler_user_show(id: UserId) -> LastModified<User> {
+///     let updated_at = user_updated_at(&id);  // `SystemTime` from the users table
+///     let user = ...;
+///     LastModified::new(updated_at, user)
+/// }
+/// ```
+///
+/// The generated server sends it as a `Last-Modified` header.  When a request has
+/// `If-Modified-Since`, and the resource hasn't changed since then (comparing at one-second
+/// precision, since that is all the header has), the server answers `304 Not Modified` with no
+/// body.  If a route has both an ETag and a modification time, `If-None-Match` takes precedence,
+/// as the HTTP spec says.
+///
+/// When the modification time can be found cheaply, without loading the whole resource (like
+/// `user_updated_at` above), it can be provided separately with
+/// `.last_modified_with(user_updated_at)`, so a `304` doesn't cost a full load.
+///
+/// The generated client's cache layer remembers `Last-Modified` from responses, sends
+/// `If-Modified-Since` on the next request for the same resource, and returns the cached value
+/// when it gets a `304`.
+fn my_api_with_last_modified() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("show")
+                    .capture("id", "UserId")
+                    .ret(GET, "User")
+                    .last_modified(),
+            ]
+        )
+}
+
+
+/// Routes can declare service level objectives with `.slo(..)`: a target availability, and
+/// latency targets at some quantiles.
+///
+/// The generated server's metrics layer records the indicators they are measured against, with
+/// the same names and labels for every route:
+///
+/// -   `saabanto_requests_total{operation, code_class}`, where availability is the share of
+///     requests that weren't `5xx`,
+/// -   `saabanto_request_duration_seconds{operation}`, a histogram whose buckets include every
+///     latency target declared on the route.
+///
+/// `generate_prometheus_rules!` then writes Prometheus recording rules for the error ratio and
+/// latency quantiles of each route with an SLO, and multi-window burn-rate alerts on its error
+/// budget:
+///
+/// ```
+/// - alert: UsersGetErrorBudgetBurn
+///   expr: |
+///     saabanto:error_ratio:rate1h{operation="users_get"} > (14.4 * 0.001)
+///     and
+///     saabanto:error_ratio:rate5m{operation="users_get"} > (14.4 * 0.001)
+///   labels: { severity: page, owner: team-identity }
+/// ```
+///
+/// The route's `.owner(..)` becomes a label on the alert, so the page goes to the right team.
+fn my_api_with_slos() -> Api {
+    Api::new()
+        .path("user")
+        .owner("team-identity")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>")
+                    .slo(
+                        Slo::availability(0.999)
+                            .latency(0.99, Duration::from_millis(300))
+                            .latency(0.5, Duration::from_millis(50))
+                    ),
+            ]
+        )
+}
+
+generate_prometheus_rules!(my_api_with_slos,
+    out_file = "monitoring/my-api-rules.yaml"
+);
+
+
+/// Deserializing a request body allocates a lot of small, short-lived things: the body buffer,
+/// unescaped strings, intermediate `Vec`s.  On our busiest routes this shows up as allocator
+/// pressure.
+///
+/// `.request_arena()` on a route gives each request a bump arena (like `bumpalo`) that the
+/// deserialization layer allocates its temporary buffers from.  When the response has been
+/// sent, the arena is reset all at once and returned to a per-worker pool, instead of freeing
+/// every allocation separately.  Nothing allocated in the arena can escape the request: the
+/// handler's arguments are still normal owned types, and only the scratch space used to build
+/// them lives in the arena.
+///
+/// Arenas that grew beyond `max_retained_size` get freed instead of pooled, the same as pooled
+/// serialization buffers.
+///
+/// Since this adds real complexity, it comes with benchmarks (`cargo bench --bench arena`)
+/// comparing throughput and allocation counts on a large nested body with and without the
+/// arena.  It should stay opt-in per route unless the benchmarks show a clear win.
+fn my_api_with_request_arena() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("bulk_update")
+                    .bulk(PUT, "User", "User", "ApiError")
+                    .request_arena(),
+            ]
+        )
+}
+
+fn serve_my_api_request_arena() {
+    Server::bind("[::]:8080")
+        .arena_pool(ArenaPool::per_worker().max_retained_size(256 * 1024))
+        .serve(my_api_server());
+}
+
+
+/// A `POST` that creates something should answer `201 Created` with a `Location` header
+/// pointing at the new resource.  Building that URL by hand in every handler is error-prone,
+/// and it ends up out of sync with the `GET` route.
+///
+/// `.created_at(route_name)` names the `GET` route for the created resource.  The handler
+/// returns a `Created<T>`, along with the values of that `GET` route's captures, and the
+/// generated server builds the `Location` URL from them, using the same path template the
+/// server routes with:
+///
+/// ```
+/// fn handler_user_new(name: Name) -> Created<User> {
+///     let user = ...;
+///     Created::at(user.id.clone(), user)
+/// }
+/// ```
+///
+/// Whether the handler returns the right captures for the named route is checked at compile
+/// time, since both are declared in the `Api`.
+///
+/// The docs show the `201` and the `Location` header, and the generated client exposes the
+/// location as a typed value, which can be passed straight to the method for the `GET` route:
+///
+/// ```
+/// let created = client.user_new(Name("Alice".to_string())).await?;
+/// let user = client.user_get(created.location.user_id).await?;
+/// ```
+fn my_api_with_created_at() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("users")
+                    .capture("user_id", "UserId")
+                    .ret(GET, "User")
+                    .name("user_get"),
+                path("users")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .name("user_new")
+                    .created_at("user_get"),
+            ]
+        )
+}
+
+
+/// Free-function handlers make shared state awkward (it ends up in globals), and there's no easy
+/// way to swap out the whole handler layer in tests.
+///
+/// `generate_server!` can also generate a trait with one method per route, with the same
+/// arguments and return types the free-function handlers would have:
+///
+/// ```
+/// trait MyApiHandlers {
+///     fn user_create(&self, userId: UserId, name: Name) -> User;
+///     fn users_get(&self, sort: bool) -> Vec<User>;
+/// }
+/// ```
+///
+/// Implementing it on a struct gives every handler access to `&self`, so the database pool and
+/// other shared state can just be fields.  `generate_server!` then takes the trait instead of a
+/// list of functions, and the function it creates takes an instance of the implementation.  Here
+/// it is named `my_api_trait_server` with `server_fn`, so it doesn't clash with the free-function
+/// `my_api_server` used everywhere else.
+///
+/// Since the trait covers every route, forgetting a handler is a compile error, and a test can
+/// implement the trait on a mock struct to stand in for the whole handler layer at once.  The
+/// free-function form keeps working for `Api`s that don't need any of this.
+struct AppHandlers {
+    db: DbPool,
+}
+
+impl MyApiHandlers for AppHandlers {
+    fn user_create(&self, userId: UserId, name: Name) -> User {
+        todo!();
+    }
+
+    fn users_get(&self, sort: bool) -> Vec<User> {
+        todo!();
+    }
+}
+
+generate_server!(my_api,
+    server_fn = my_api_trait_server,
+    handler_trait = MyApiHandlers
+);
+
+fn serve_my_api_with_trait() {
+    Server::bind("[::]:8080")
+        .serve(my_api_trait_server(AppHandlers { db: db_pool() }));
+}
+
+
+/// JSON is always UTF-8, but whether the generated server says so on `Content-Type` currently
+/// depends on the backend.  It should always be explicit:
+///
+/// -   JSON responses are sent as `application/json; charset=utf-8`, and text responses as
+///     `text/plain; charset=utf-8`, on every backend.
+/// -   A request body with a `charset` other than `utf-8` is rejected with `415`, instead of being
+///     decoded as UTF-8 anyway and mangling the text.
+/// -   On routes that don't declare other charsets (see below), an `Accept-Charset` that
+///     excludes UTF-8 gets `406 Not Acceptable`.
+///
+/// These become cases in the backend conformance suite, so every backend has to agree on them.
+///
+/// Routes returning text can declare other charsets they are able to send, for the rare consumer
+/// that can't handle UTF-8.  The handler still returns a normal `String`, and the server encodes
+/// it into the charset negotiated from `Accept-Charset` (with `encoding_rs`), rejecting the
+/// response with a `500` if it has a character the charset can't represent.
+fn my_api_with_charsets() -> Api {
+    Api::new()
+        .path("legacy")
+        .alt(
+            alts![
+                path("export")
+                    .capture("id", "ExportId")
+                    .ret_text(GET)
+                    .charsets(&["utf-8", "iso-8859-1"]),
+            ]
+        )
+}
+
+
+/// Consumers usually want some normalization applied to every response the same way, like
+/// converting timestamps to UTC or sorting lists, and end up doing it at every call site.
+///
+/// A `ResponseTransform` is registered on the client for an operation, and runs on every
+/// successful response of that operation after it has been decoded:
+///
+/// ```
+/// trait ResponseTransform<T> {
+///     fn transform(&self, value: T) -> T;
+/// }
+/// ```
+///
+/// Transforms are typed: a transform for `users_get` has to be a `ResponseTransform<Vec<User>>`,
+/// so registering one for the wrong operation doesn't compile.  Several transforms on the same
+/// operation run in the order they were registered.  Transforms never see error responses, and
+/// can't turn a success into an error.
+///
+/// The same transform can also be registered for every operation returning a given type, with
+/// `.transform_all::<User>(..)`, which also applies to `User`s inside `Vec`s and `Option`s.
+struct SortUsersByName;
+
+impl ResponseTransform<Vec<User>> for SortUsersByName {
+    fn transform(&self, mut users: Vec<User>) -> Vec<User> {
+        users.sort_by(|a, b| a.name.0.cmp(&b.name.0));
+        users
+    }
+}
+
+fn my_api_client_transforms() -> Client {
+    Client::builder("https://api.example.com")
+        .transform("users_get", SortUsersByName)
+        .build()
+}
+
+
+/// We publish external docs for production, and internal docs for staging, from the same `Api`.
+/// `generate_docs!` can take the environment to generate for, and uses it to pick:
+///
+/// -   which server URLs are listed (just that environment's, from `.env(..)`),
+/// -   the auth instructions, since each environment can document its own way of getting
+///     credentials with `.env_auth_docs(..)`,
+/// -   which routes appear.  Experimental routes, and routes tagged with a tag listed in
+///     `hide_tags`, are left out of the production docs.
+///
+/// Everything else, including the route descriptions and examples, is the same for every
+/// environment, so the two sets of docs can't drift apart.
+///
+/// The OpenAPI generator takes the same `env` parameter, with the same effect.
+generate_docs!(my_api_with_envs,
+    env = "prod",
+    hide_tags = ["internal"],
+    out_dir = "docs/prod"
+);
+
+generate_docs!(my_api_with_envs,
+    env = "staging",
+    out_dir = "docs/staging"
+);
+
+
+/// Much of what the `Api` declares (deadlines, body size limits, compression, concurrency limits)
+/// already has a tower-http layer that does the actual work.  With the tower backend, these layers
+/// can be set up from the `Api` instead of being configured a second time by hand.
+///
+/// `TowerLayers::from_api` builds a stack with:
+///
+/// -   `TraceLayer`, with span names and fields taken from the route (operation name, owner),
+///     and the route's `.sampling(..)` applied,
+/// -   `CompressionLayer`, using the compression declared on the `Api` and routes,
+/// -   `TimeoutLayer`, with each route's maximum deadline,
+/// -   `ConcurrencyLimitLayer` and `RequestBodyLimitLayer`, from the declared limits.
+///
+/// Each layer can be turned off separately, for applications that already have their own.  The
+/// per-route settings are applied by looking up the matched route on each request, so one stack
+/// serves the whole `Api`.
+fn serve_my_api_tower_layers() {
+    let api = my_api_with_deadlines();
+
+    Server::bind("[::]:8080")
+        .layer(TowerLayers::from_api(&api).without_compression())
+        .serve(my_api_server());
+}
+
+
+/// Lots of services built on this crate also have to serve a frontend bundle, and end up pulling
+/// in a second web framework just for that.  `.static_dir(..)` is a route kind serving a whole
+/// directory of files under a path prefix.
+///
+/// It reuses what file routes already do: `ETag`s (from the file's size and modification time),
+/// `Last-Modified`, `Range` requests, and conditional requests.  Content types are picked from
+/// the file extension.  Paths that try to escape the directory (`..`, symlinks pointing outside
+/// it, percent-encoded tricks) get `404`.
+///
+/// For single-page apps, `.spa_fallback("index.html")` serves `index.html` for any path that
+/// doesn't match a file, so client-side routing works.
+///
+/// Files can be served straight from disk, or cached:
+///
+/// -   `StaticCache::Memory` reads every file into memory at startup,
+/// -   `StaticCache::Mmap` memory-maps files on first use, which is better for big directories,
+/// -   `StaticCache::None` reads from disk on every request, for development.
+///
+/// These routes show up in the route table and docs, but not in the generated clients.
+fn my_api_with_static() -> Api {
+    Api::new()
+        .alt(
+            alts![
+                path("user")
+                    .path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+                static_dir("/assets", "./frontend/dist")
+                    .cache(StaticCache::Mmap)
+                    .spa_fallback("index.html"),
+            ]
+        )
+}
+
+
+/// Deprecating a route is easy, but removing it safely requires knowing nobody still calls it.
+///
+/// Whenever a deprecated route (or a route past its sunset date that keeps serving) is called,
+/// the generated server records a `DeprecatedCall`: the route name, the principal if there is
+/// one, and the SDK name and version from the telemetry headers.  Calls are counted in memory and
+/// flushed to a pluggable `DeprecationSink` every so often, so recording doesn't cost anything on
+/// the request path.
+///
+/// `deprecation_report` aggregates what the sink has stored, per route, into who is still calling
+/// and when they last did:
+///
+/// ```
+/// users_get (deprecated, sunset 2021-06-01)
+///     billing-service        sdk rust 1.2.0    41,203 calls   last seen 2021-03-02
+///     mobile-app             sdk ts 0.9.1         311 calls   last seen 2021-02-27
+/// ```
+///
+/// When a route hasn't been called for long enough, it's safe to remove.
+fn serve_my_api_deprecation_telemetry() {
+    Server::bind("[::]:8080")
+        .deprecation_sink(PostgresDeprecationSink::new(db_pool()))
+        .serve(my_api_server());
+}
+
+
+/// A streaming route holds on to its connection for as long as the client stays connected, which
+/// can be forever.  Without a limit, enough open SSE connections will use up every file
+/// descriptor the process has.
+///
+/// Streaming routes (SSE, websockets, duplex) can declare:
+///
+/// -   `.max_connections(n)`: at most `n` streams open on this route at once, per server
+///     instance.  Groups of routes can also share one limit.
+/// -   `.idle_timeout(d)`: a stream that hasn't sent or received anything for `d` is closed.  For
+///     SSE, a keep-alive comment doesn't count as activity, so abandoned streams still get closed.
+/// -   `.on_connection_limit(..)`: what to do with a new connection when the limit is reached.
+///     `ConnectionLimit::Reject` answers `503` with `Retry-After`, and
+///     `ConnectionLimit::EvictOldest` closes the oldest open stream to make room.
+///
+/// The number of open streams per route, and how many were rejected or timed out, are exposed in
+/// metrics.
+fn my_api_with_stream_limits() -> Api {
+    Api::new()
+        .path("events")
+        .alt(
+            alts![
+                path("stream")
+                    .ret_sse(GET, "Event")
+                    .max_connections(10_000)
+                    .idle_timeout(Duration::from_secs(300))
+                    .on_connection_limit(ConnectionLimit::Reject),
+            ]
+        )
+}
+
+
+/// Examples are written as JSON, next to the types they illustrate, so nothing stops them from
+/// going out of date when the type changes.  Fixtures catch some of this at compile time, but
+/// only for examples that go through them.
+///
+/// `generate_example_tests!` creates one test per example.  Each test:
+///
+/// 1.  validates the example JSON against the schema generated for its type, the same schema the
+///     OpenAPI output has,
+/// 2.  deserializes it into the Rust type with the route's codec settings,
+/// 3.  serializes it again, and checks that the result is the same JSON (ignoring key order), so
+///     examples can't rely on fields that are silently dropped.
+///
+/// A failing test names the route, the example, and what didn't match:
+///
+/// ```
+/// ---- example_user_create_response stdout ----
+/// user_create: example response doesn't match `User`:
+///     /name: expected a string, found 123
+/// ```
+///
+/// Running this in CI means the published docs never show a payload the API would reject.
+generate_example_tests!(my_api_with_examples);
+
+
+/// Some mistakes only show up when you look at several routes together: a resource you can
+/// create but never fetch, or list routes that disagree on what the page parameter is called.
+///
+/// `ApiLints` checks rules like these over the whole `Api` when it is generated.  A few rules are
+/// built in:
+///
+/// -   `CreateHasGet`: every route with `.created_at(..)` or a `POST` creating a resource under a
+///     path has a matching `GET` by id.
+/// -   `ConsistentPagination`: every `.paginated(..)` route uses the same parameter names.
+/// -   `ResourceHasDelete`: every resource group has a `DELETE` route.
+/// -   `RequireOwner`: every route has an `.owner(..)`.
+///
+/// Organizations can add their own rules by implementing `ApiLint`, which gets the same
+/// `RouteInfo`s as `Api::routes()`.  Each rule can be set to warn or deny, and can be allowed for
+/// a single route with `.allow_lint(..)`.
+///
+/// Each finding is reported with the routes that broke the rule, and how to fix it.  Only
+/// deny-level findings stop generation; warn-level ones are printed as warnings, and generation
+/// carries on:
+///
+/// ```
+/// warning: lint `ResourceHasDelete` failed for group `users`
+///     `users` has `user_get` and `user_new`, but no DELETE route.
+///     Add one, or allow it with `.allow_lint("ResourceHasDelete")` on the group.
+/// ```
+///
+/// The lints are passed as an option on the existing `generate_server!` call:
+///
+/// ```
+/// generate_server!(my_api_with_lints,
+///     lints = my_api_lints,
+///     server_alts![
+///         handler_user_get,
+///         handler_user_new,
+///     ]
+/// );
+/// ```
+fn my_api_lints() -> ApiLints {
+    ApiLints::new()
+        .deny(CreateHasGet)
+        .deny(ConsistentPagination)
+        .warn(ResourceHasDelete)
+        .deny(RequireOwner)
+}
+
+fn my_api_with_lints() -> Api {
+    Api::new()
+        .owner("team-identity")
+        .alt(
+            alts![
+                path("users")
+                    .capture("user_id", "UserId")
+                    .ret(GET, "User")
+                    .name("user_get"),
+                path("users")
+                    .body("name", "Name")
+                    .ret(POST, "User")
+                    .name("user_new")
+                    .created_at("user_get"),
+            ]
+        )
+}
+
+
+/// For streaming and chunked responses, some things are only known once the whole body has been
+/// sent, like a checksum or the number of rows.  HTTP trailers are the place for them.
+///
+/// Routes can declare trailers with `.trailer(..)`, along with their types.  From these, the
+/// generator derives a struct named after the route, with one `Option` field per trailer, named
+/// after the header in snake case without the `X-` prefix:
+///
+/// ```
+/// struct ExportStreamTrailers {
+///     row_count: Option<u64>,    // X-Row-Count
+///     checksum: Option<Sha256>,  // X-Checksum
+/// }
+/// ```
+///
+/// The handler takes a `Trailers` handle as an argument, and moves it into its stream, which sets
+/// the values as it goes.  The generated server sends them after the last chunk:
+///
+/// ```
+/// fn handler_export_stream(
+///     id: ExportId,
+///     trailers: Trailers<ExportStreamTrailers>,
+/// ) -> impl Stream<Item = Row> {
+///     stream! {
+///         let mut row_count = 0;
+///         for row in load_rows(id) {
+///             row_count += 1;
+///             yield row;
+///         }
+///         trailers.set(|t| t.row_count = Some(row_count));
+///     }
+/// }
+/// ```
+///
+/// Trailers are announced in a `Trailer` header up front.  A trailer that the handler never sets
+/// is left out, so the client sees it as missing rather than as an empty value.
+///
+/// The generated client exposes them as typed fields once the stream has been read to the end:
+///
+/// ```
+/// let mut rows = client.export_stream(ExportId(42)).await?;
+/// while let Some(row) = rows.next().await { ... }
+/// let trailers: ExportStreamTrailers = rows.trailers().await?;
+/// ```
+///
+/// HTTP/1.1 only allows trailers with chunked encoding, and some proxies drop them, so the docs
+/// note this on every route that uses them.
+fn my_api_with_trailers() -> Api {
+    Api::new()
+        .path("export")
+        .alt(
+            alts![
+                path("stream")
+                    .capture("id", "ExportId")
+                    .ret_stream(GET, "Row")
+                    .trailer("X-Row-Count", "u64")
+                    .trailer("X-Checksum", "Sha256"),
+            ]
+        )
+}
+
+
+/// Old SDKs stay in the wild for years.  To be able to retire them in a controlled way, every
+/// generated client embeds the `Api` version it was generated from, and sends it with each
+/// request:
+///
+/// ```
+/// Api-Version: 1.4.0
+/// ```
+///
+/// The server can then be given a minimum client version, and what to do about clients below it:
+///
+/// -   `OldClients::Warn` serves the request as usual, but adds an `Api-Min-Version: 1.2.0`
+///     header (which the generated client logs once per process) and records the call like a
+///     deprecated route.
+/// -   `OldClients::Reject` answers `400 Bad Request` with a `client-too-old` problem type and
+///     the minimum version, which the generated client turns into `ClientError::ClientTooOld`.
+///
+/// Requests without the header (curl, hand-written clients) are always let through, since the
+/// point is to phase out our own SDKs, not to require every caller to send it.
+///
+/// Going the other way, the client's `capabilities()` report includes the server's `Api`
+/// version, so a client can check it is talking to a server at least as new as it needs.
+fn serve_my_api_min_client_version() {
+    Server::bind("[::]:8080")
+        .min_client_version("1.2.0", OldClients::Warn)
+        .serve(my_api_server());
+}
+
+
+/// A query parameter can be in three states, and they get mixed up easily:
+///
+/// -   `?sort`: present, with no value,
+/// -   `?sort=`: present, with an empty value,
+/// -   (nothing): absent.
+///
+/// Our older clients send flag-style parameters like `?sort`, and the current `bool` decoding
+/// reads them as `false`.  Parameters should have an explicit choice about how these map to
+/// their type:
+///
+/// -   `QueryFlag` is for flag-style parameters.  `?sort` and `?sort=true` are `true`, absent and
+///     `?sort=false` are `false`, and `?sort=` is rejected with `400`.
+/// -   `QueryValue<T>` keeps every state apart, for parameters where the difference matters:
+///
+///     ```
+///     enum QueryValue<T> {
+///         Absent,    // (nothing)
+///         Flag,      // ?filter
+///         Empty,     // ?filter=
+///         Value(T),  // ?filter=active
+///     }
+///     ```
+///
+/// -   For everything else, `.empty_value(..)` says whether an empty value means absent, counts
+///     as an empty string, or is rejected.
+///
+/// The meaning is documented on each parameter, and the generated client encodes each state
+/// the same way the server decodes it (sending `QueryFlag` as `?sort` when true, and leaving it
+/// out when false).
+fn my_api_with_query_flags() -> Api {
+    Api::new()
+        .path("user")
+        .alt(
+            alts![
+                path("get")
+                    .query("sort", "QueryFlag")
+                    .query("filter", "QueryValue<String>")
+                    .query("cursor", "Option<String>")
+                    .empty_value("cursor", EmptyValue::Absent)
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}
+
+
+/// A `UserId(u32)` on the wire gives away how many users we have, and how fast we are growing.
+/// Handlers should keep using the plain numeric ids, but the API should only ever show opaque
+/// tokens.
+///
+/// Types can be declared as obfuscated on the `Api` with `.obfuscate::<UserId>(..)`, along with
+/// an `IdCodec` that turns the number into a token and back:
+///
+/// ```
+/// trait IdCodec {
+///     fn encode(&self, id: u64) -> String;
+///     fn decode(&self, token: &str) -> Option<u64>;
+/// }
+/// ```
+///
+/// The built-in `Hashids` codec produces short tokens like `"jR3kXw"`, and `EncryptedIds` uses a
+/// block cipher for tokens that can't be reversed without the key.  Each type gets its own salt
+/// or key, so a `UserId` token can't be passed off as an `InvoiceId`.
+///
+/// The conversion happens at the serialization boundary only.  Captures, query parameters, and
+/// fields of the declared types are decoded from tokens before the handler sees them, and encoded
+/// to tokens when responses are serialized.  A token that doesn't decode is treated like any other
+/// value that didn't parse: in a capture it follows the route's `InvalidCapture` policy, in a
+/// query parameter it is rejected with `400`, and in a body field it gets the usual
+/// `invalid_body` error, with a pointer to the field.
+///
+/// The docs and OpenAPI output describe these as opaque strings, and the generated clients treat
+/// them that way too.
+fn my_api_with_obfuscated_ids() -> Api {
+    Api::new()
+        .obfuscate::<UserId>(Hashids::new(user_id_salt()).min_length(6))
+        .obfuscate::<InvoiceId>(EncryptedIds::new(invoice_id_key()))
+        .path("user")
+        .alt(
+            alts![
+                path("create")
+                    .capture("id", "UserId")
+                    .body("name", "Name")
+                    .ret(POST, "User"),
+                path("get")
+                    .query("sort", "bool")
+                    .ret(GET, "Vec<User>"),
+            ]
+        )
+}